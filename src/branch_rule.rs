@@ -0,0 +1,181 @@
+use regex::Regex;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::check_prerelease_identifier;
+use crate::error::{MinverError, Result};
+use crate::metadata::slugify;
+
+const BRANCH_SLUG_PLACEHOLDER: &str = "{branch_slug}";
+/// The pattern that matches a detached `HEAD`. Git does not allow branches with this name.
+const DETACHED_PATTERN: &str = "HEAD";
+
+/// Maps branches to the prerelease identifier that is used for their untagged commits.
+///
+/// Parsed from `<pattern>=<identifier>`, e.g. `release/*=rc`. The pattern is the name of a branch
+/// in which `*` matches any sequence of characters, or `HEAD` to match a detached `HEAD`. The
+/// identifier can contain `{branch_slug}`, which is replaced with the branch name with every
+/// character other than ASCII letters and digits replaced with `-`, e.g.
+/// `feature/*=feat-{branch_slug}`. Leading zeros are removed from numeric identifiers, so a
+/// branch named `007` becomes `rc.7` with `*=rc.{branch_slug}`.
+#[derive(Debug, Clone)]
+pub struct BranchRule {
+    pattern: String,
+    regex: Regex,
+    identifier: String,
+}
+
+impl BranchRule {
+    /// Returns the prerelease identifier for `branch`, or `None` if the rule does not match.
+    /// `branch` is `None` for a detached `HEAD`.
+    pub(crate) fn prerelease_identifier(&self, branch: Option<&str>) -> Result<Option<String>> {
+        let matches = match branch {
+            Some(branch) => self.regex.is_match(branch),
+            None => self.pattern == DETACHED_PATTERN,
+        };
+        if !matches {
+            return Ok(None);
+        }
+
+        let slug = branch.map_or(String::from(DETACHED_PATTERN), slugify);
+        let identifier =
+            trim_leading_zeros(&self.identifier.replace(BRANCH_SLUG_PLACEHOLDER, &slug));
+        check_prerelease_identifier("branch_rules", &identifier)?;
+        Ok(Some(identifier))
+    }
+}
+
+/// Removes the leading zeros of numeric identifiers, which semver does not allow, e.g. when the
+/// slug of a branch named `007` is a separate identifier in `rc.{branch_slug}`.
+fn trim_leading_zeros(identifier: &str) -> String {
+    let identifiers: Vec<&str> = identifier
+        .split('.')
+        .map(|part| match part.trim_start_matches('0') {
+            "" if !part.is_empty() => "0",
+            trimmed if trimmed.bytes().all(|b| b.is_ascii_digit()) => trimmed,
+            _ => part,
+        })
+        .collect();
+    identifiers.join(".")
+}
+
+impl FromStr for BranchRule {
+    type Err = MinverError;
+
+    fn from_str(rule: &str) -> Result<BranchRule> {
+        let invalid =
+            |reason: &str| MinverError::InvalidBranchRule(format!("{} ({})", rule, reason));
+
+        let (pattern, identifier) = match rule.split_once('=') {
+            Some((pattern, identifier)) if !pattern.is_empty() => (pattern, identifier),
+            _ => return Err(invalid("must be in the form <pattern>=<identifier>")),
+        };
+        check_prerelease_identifier(
+            "branch_rules",
+            &identifier.replace(BRANCH_SLUG_PLACEHOLDER, "branch"),
+        )
+        .map_err(|_| {
+            invalid(&format!(
+                "{} is not a valid prerelease identifier",
+                identifier
+            ))
+        })?;
+
+        let expression: Vec<String> = pattern.split('*').map(regex::escape).collect();
+        let regex = Regex::new(&format!("^{}$", expression.join(".*")))
+            .map_err(|e| invalid(&e.to_string()))?;
+
+        Ok(BranchRule {
+            pattern: String::from(pattern),
+            regex,
+            identifier: String::from(identifier),
+        })
+    }
+}
+
+impl fmt::Display for BranchRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.pattern, self.identifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rule_matches_branch_pattern() {
+        let rule: BranchRule = "release/*=rc".parse().unwrap();
+
+        assert_eq!(
+            Some(String::from("rc")),
+            rule.prerelease_identifier(Some("release/1.2")).unwrap()
+        );
+        assert_eq!(None, rule.prerelease_identifier(Some("main")).unwrap());
+        assert_eq!(
+            None,
+            rule.prerelease_identifier(Some("x/release/1")).unwrap()
+        );
+        assert_eq!(None, rule.prerelease_identifier(None).unwrap());
+    }
+
+    #[test]
+    fn test_rule_replaces_branch_slug() {
+        let rule: BranchRule = "feature/*=feat-{branch_slug}".parse().unwrap();
+
+        assert_eq!(
+            Some(String::from("feat-feature-my-change")),
+            rule.prerelease_identifier(Some("feature/my_change"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_numeric_branch_slug_has_no_leading_zeros() {
+        let rule: BranchRule = "*=rc.{branch_slug}".parse().unwrap();
+
+        assert_eq!(
+            Some(String::from("rc.7")),
+            rule.prerelease_identifier(Some("007")).unwrap()
+        );
+        assert_eq!(
+            Some(String::from("rc.0")),
+            rule.prerelease_identifier(Some("00")).unwrap()
+        );
+        assert_eq!(
+            Some(String::from("rc.release-007")),
+            rule.prerelease_identifier(Some("release/007")).unwrap()
+        );
+        assert_eq!(
+            Some(String::from("rc.0a")),
+            rule.prerelease_identifier(Some("0a")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detached_head_rule() {
+        let rule: BranchRule = "HEAD=ci".parse().unwrap();
+
+        assert_eq!(
+            Some(String::from("ci")),
+            rule.prerelease_identifier(None).unwrap()
+        );
+        assert_eq!(None, rule.prerelease_identifier(Some("main")).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!("main".parse::<BranchRule>().is_err());
+        assert!("=alpha".parse::<BranchRule>().is_err());
+        assert!("main=".parse::<BranchRule>().is_err());
+        assert!("main=al_pha".parse::<BranchRule>().is_err());
+        assert_eq!(
+            "feature/*=feat-{branch_slug}",
+            "feature/*=feat-{branch_slug}"
+                .parse::<BranchRule>()
+                .unwrap()
+                .to_string()
+        );
+    }
+}
@@ -1,21 +1,34 @@
-use anyhow::Result;
 use git2::Repository;
+use regex::Regex;
+use std::collections::HashMap;
 use std::env;
 use std::ffi::OsString;
+use std::fmt;
 use std::fs;
-use std::path::Path;
-use toml_edit::{value, Document};
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{value, Document, Item, Table, Value};
 
-use crate::MinverConfig;
+use crate::error::{MinverError, Result};
+use crate::minver_core;
+use crate::version_source;
+use crate::{MinverConfig, Version};
 
 /// Environment variable that determines if minver_rs will update the crate version. If this
 /// variable is not set, then no action will be taken.
 pub const UPDATE_VERSION_VAR: &str = "MINVER_UPDATE_VERSION";
 
+/// The name of the file written to `OUT_DIR` by [`rustc_env_build_action()`], which declares the
+/// version as constants. See [`BuildInfo::version_module`] for details.
+pub const VERSION_MODULE_FILE: &str = "minver_version.rs";
+
+/// The tables of a manifest that contain dependencies.
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
 /// Updates the version in `Cargo.toml`.
 /// 
 /// This function prints `cargo:rerun-if` output to ensure that this build action will be run when
-/// called in `build.rs`.
+/// called in `build.rs` and a commit is made, a tag is created or the configuration changes, see
+/// [`get_rerun_if_changed`].
 /// 
 /// ```no_run
 /// use minver_rs::build_utils;
@@ -27,14 +40,8 @@ pub const UPDATE_VERSION_VAR: &str = "MINVER_UPDATE_VERSION";
 /// }
 /// ```
 pub fn default_build_action() {
-    println!("cargo:rerun-if-changed=.git/refs/tags/");
     println!("cargo:rerun-if-env-changed={}", UPDATE_VERSION_VAR);
-
-    let config = MinverConfig::read_from_env().expect("Failed to parse configuration");
-    if let Err(e) = simple_logger::init_with_level(config.log_level) {
-        println!("Failed to initialize log: {}", e);
-    }
-
+    let config = init_build_action();
     default_build_action_silent(&config);
 }
 
@@ -54,30 +61,857 @@ pub fn default_build_action_silent(config: &MinverConfig) {
     }
 }
 
+/// Emits the version as `cargo:rustc-env` variables instead of updating `Cargo.toml`.
+/// 
+/// The manifest and the working tree are left untouched, so this works with `cargo publish
+/// --locked` and read-only sources. The version is available to the crate with
+/// `env!("MINVER_VERSION")`, see [`BuildInfo::rustc_env`] for every variable, and as constants
+/// in [`VERSION_MODULE_FILE`] in `OUT_DIR`. This function prints `cargo:rerun-if` output to
+/// ensure that the variables are updated when a commit is made, a tag is created or the
+/// configuration changes, see [`get_rerun_if_changed`].
+/// 
+/// ```no_run
+/// use minver_rs::build_utils;
+/// 
+/// fn main() {
+///     build_utils::rustc_env_build_action();
+/// }
+/// ```
+pub fn rustc_env_build_action() {
+    let config = init_build_action();
+    rustc_env_build_action_silent(&config);
+}
+
+/// Reads the configuration from the environment, initializes the log and prints the
+/// `cargo:rerun-if` output shared by the build actions: every configuration variable and the
+/// files from [`get_rerun_if_changed`].
+fn init_build_action() -> MinverConfig {
+    for name in MinverConfig::env_var_names() {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+
+    let config = MinverConfig::read_from_env().expect("Failed to parse configuration");
+    if let Err(e) = simple_logger::init_with_level(config.log_level) {
+        println!("Failed to initialize log: {}", e);
+    }
+
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap_or(OsString::from("."));
+    for path in get_rerun_if_changed(&manifest_dir, &config) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    config
+}
+
+/// Gets the files of the repository containing `manifest_dir` that change when a commit is made
+/// or a tag is created: `HEAD`, the branch that `HEAD` points to, `packed-refs` and `refs/tags`.
+/// Files that do not exist are skipped, since cargo would rerun the build script on every build.
+/// Returns nothing if `manifest_dir` is not in a repository.
+pub fn get_rerun_if_changed(manifest_dir: &OsString, config: &MinverConfig) -> Vec<PathBuf> {
+    let repo = match crate::open_repository(Path::new(manifest_dir), config) {
+        Ok(repo) => repo,
+        Err(e) => {
+            log::debug!("{}, not watching any repository files", e);
+            return vec![];
+        }
+    };
+    // `HEAD` is specific to a worktree, while the refs are shared with the main repository
+    let git_dir = repo.path();
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    };
+
+    let mut paths = vec![git_dir.join("HEAD")];
+    if let Some(target) = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(String::from))
+    {
+        paths.push(common_dir.join(target));
+    }
+    paths.push(common_dir.join("packed-refs"));
+    paths.push(common_dir.join("refs").join("tags"));
+    paths.into_iter().filter(|path| path.exists()).collect()
+}
+
+/// Emits the version as `cargo:rustc-env` variables without printing any `cargo:rerun-if` output.
+/// If `OUT_DIR` is set, the version is also written to [`VERSION_MODULE_FILE`].
+pub fn rustc_env_build_action_silent(config: &MinverConfig) {
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap_or(OsString::from("."));
+    let build_info = get_build_info(&manifest_dir, config).unwrap();
+    for (name, value) in build_info.rustc_env() {
+        println!("cargo:rustc-env={}={}", name, value);
+    }
+    if let Some(out_dir) = env::var_os("OUT_DIR") {
+        build_info
+            .write_version_module(Path::new(&out_dir))
+            .unwrap();
+    }
+}
+
+/// Gets the environment variables emitted by [`rustc_env_build_action()`]. See
+/// [`BuildInfo::rustc_env`] for details.
+pub fn get_rustc_env(
+    manifest_dir: &OsString,
+    config: &MinverConfig,
+) -> Result<Vec<(&'static str, String)>> {
+    Ok(get_build_info(manifest_dir, config)?.rustc_env())
+}
+
+/// The version of a crate being built, along with the commit it was calculated for.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildInfo {
+    /// The version.
+    pub version: Version,
+    /// The number of commits since the latest tag, or `0` if the version was not calculated from
+    /// a repository.
+    pub height: u32,
+    /// The id of the commit, or `None` if the version was not calculated from a repository.
+    pub commit: Option<String>,
+    /// The time of the commit in seconds since the unix epoch, or `None` if the version was not
+    /// calculated from a repository.
+    pub commit_timestamp: Option<i64>,
+}
+
+impl BuildInfo {
+    fn without_commit(version: Version) -> BuildInfo {
+        BuildInfo {
+            version,
+            height: 0,
+            commit: None,
+            commit_timestamp: None,
+        }
+    }
+
+    /// Gets the environment variables emitted by [`rustc_env_build_action()`]:
+    /// 
+    /// - `MINVER_VERSION`: the full version, e.g. `1.2.4-alpha.3+build.5`
+    /// - `MINVER_MAJOR`, `MINVER_MINOR` and `MINVER_PATCH`: the version components
+    /// - `MINVER_PRERELEASE` and `MINVER_METADATA`: the prerelease identifiers and build
+    ///   metadata, without the leading `-` or `+`, or empty if the version has none
+    /// - `MINVER_HEIGHT`: the number of commits since the latest tag
+    /// - `MINVER_SHA`: the id of the commit, or empty if it is not known
+    pub fn rustc_env(&self) -> Vec<(&'static str, String)> {
+        let version = &self.version;
+        vec![
+            ("MINVER_VERSION", version.to_string()),
+            ("MINVER_MAJOR", version.major.to_string()),
+            ("MINVER_MINOR", version.minor.to_string()),
+            ("MINVER_PATCH", version.patch.to_string()),
+            (
+                "MINVER_PRERELEASE",
+                version.prerelease.clone().unwrap_or_default(),
+            ),
+            (
+                "MINVER_METADATA",
+                version.build_metadata.clone().unwrap_or_default(),
+            ),
+            ("MINVER_HEIGHT", self.height.to_string()),
+            ("MINVER_SHA", self.commit.clone().unwrap_or_default()),
+        ]
+    }
+
+    /// Gets the contents of [`VERSION_MODULE_FILE`], which declares the version as constants:
+    /// 
+    /// ```ignore
+    /// pub const VERSION: &str = "1.2.4-alpha.3";
+    /// pub const MAJOR: u64 = 1;
+    /// pub const MINOR: u64 = 2;
+    /// pub const PATCH: u64 = 4;
+    /// pub const PRERELEASE: Option<&str> = Some("alpha.3");
+    /// pub const BUILD_METADATA: Option<&str> = None;
+    /// pub const IS_PRERELEASE: bool = true;
+    /// pub const HEIGHT: u32 = 3;
+    /// pub const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+    /// pub const COMMIT_TIMESTAMP: Option<i64> = Some(1700000000);
+    /// ```
+    /// 
+    /// `COMMIT` is empty and `COMMIT_TIMESTAMP` is `None` if the commit is not known.
+    pub fn version_module(&self) -> String {
+        let version = &self.version;
+        let optional_str = |value: &Option<String>| match value {
+            Some(value) => format!("Some({:?})", value),
+            None => String::from("None"),
+        };
+        let mut module = String::from("// Generated by minver_rs, do not edit.\n");
+        let mut constant = |name: &str, ty: &str, value: String| {
+            module.push_str(&format!("pub const {}: {} = {};\n", name, ty, value))
+        };
+
+        constant("VERSION", "&str", format!("{:?}", version.to_string()));
+        constant("MAJOR", "u64", version.major.to_string());
+        constant("MINOR", "u64", version.minor.to_string());
+        constant("PATCH", "u64", version.patch.to_string());
+        constant(
+            "PRERELEASE",
+            "Option<&str>",
+            optional_str(&version.prerelease),
+        );
+        constant(
+            "BUILD_METADATA",
+            "Option<&str>",
+            optional_str(&version.build_metadata),
+        );
+        constant(
+            "IS_PRERELEASE",
+            "bool",
+            version.prerelease.is_some().to_string(),
+        );
+        constant("HEIGHT", "u32", self.height.to_string());
+        constant(
+            "COMMIT",
+            "&str",
+            format!("{:?}", self.commit.as_deref().unwrap_or_default()),
+        );
+        constant(
+            "COMMIT_TIMESTAMP",
+            "Option<i64>",
+            match self.commit_timestamp {
+                Some(timestamp) => format!("Some({})", timestamp),
+                None => String::from("None"),
+            },
+        );
+        module
+    }
+
+    /// Writes [`BuildInfo::version_module`] to [`VERSION_MODULE_FILE`] in `out_dir` and returns
+    /// the path of the file.
+    /// 
+    /// ```ignore
+    /// mod build_info {
+    ///     include!(concat!(env!("OUT_DIR"), "/minver_version.rs"));
+    /// }
+    /// ```
+    pub fn write_version_module(&self, out_dir: &Path) -> Result<PathBuf> {
+        let path = out_dir.join(VERSION_MODULE_FILE);
+        fs::write(&path, self.version_module()).map_err(|source| MinverError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        log::debug!("Wrote version module to {:?}", path);
+        Ok(path)
+    }
+}
+
+/// Gets the version of the crate in `manifest_dir` without updating `Cargo.toml`.
+/// 
+/// If no version can be found, e.g. because a packaged crate is built, the version in
+/// `Cargo.toml` is used.
+pub fn get_build_info(manifest_dir: &OsString, config: &MinverConfig) -> Result<BuildInfo> {
+    let manifest_path = Path::new(manifest_dir).join("Cargo.toml");
+    let document = read_manifest(&manifest_path)?;
+    let build_info = match get_package_build_info(Path::new(manifest_dir), &document, config)? {
+        Some(build_info) => build_info,
+        None => {
+            // Cargo defaults the version of a package to 0.0.0 if it is not set
+            let version = get_str(&document, &["package", "version"]);
+            BuildInfo::without_commit(Version::parse(version.unwrap_or("0.0.0"))?)
+        }
+    };
+    log::debug!("Using build info {:?}", build_info);
+    Ok(build_info)
+}
+
 /// Updates the version in `Cargo.toml`.
+/// 
+/// If the crate is not in a repository, e.g. because it is built from a source tarball, the
+/// version is read from the other [`crate::VersionSource`]s instead. A packaged crate keeps the
+/// version in its manifest, even if it is vendored inside another repository. The version of the
+/// package is also updated in the `Cargo.lock` of the package or its workspace, if there is one.
+/// If the package inherits its version with `version.workspace = true`, the version in
+/// `[workspace.package]` of the workspace root is updated instead.
 pub fn update_package_version(manifest_dir: &OsString, config: &MinverConfig) -> Result<()> {
     let manifest_path = Path::new(manifest_dir).join("Cargo.toml");
     log::debug!("Will update manifest file at {:?}", manifest_path);
 
-    let mut document: Document = fs::read_to_string(&manifest_path)?.parse::<Document>()?;
-    log::debug!("Successfully read manifest file");
+    let document = read_manifest(&manifest_path)?;
+
+    let version = match get_package_version(Path::new(manifest_dir), &document, config)? {
+        Some(version) => version,
+        None => return Ok(()),
+    };
+
+    let (version_path, mut version_document, table_keys) =
+        read_version_manifest(Path::new(manifest_dir), &manifest_path, &document)?;
+    let table = table_keys
+        .iter()
+        .fold(version_document.as_item_mut(), |item, key| &mut item[*key]);
+    set_value(table, "version", &version.to_string());
+    log::debug!("Updated version in {:?} to {}", version_path, version);
+
+    write_manifest(&version_path, &version_document)?;
+    if let Some(name) = get_str(&document, &["package", "name"]) {
+        let versions = vec![(String::from(name), version)].into_iter().collect();
+        update_lockfile(Path::new(manifest_dir), &versions, true)?;
+    }
+    Ok(())
+}
+
+/// Checks that the version in `Cargo.toml` matches the calculated version without updating it.
+/// If there is a `Cargo.lock` for the package, the version of the package in it is checked as
+/// well. Returns every version that is out of date, or an empty list if the versions match. An
+/// inherited version is checked in `[workspace.package]` of the workspace root.
+/// 
+/// Like [`update_package_version`], nothing is checked if the crate has no version source, e.g.
+/// when a packaged crate is built.
+pub fn check_package_version(
+    manifest_dir: &OsString,
+    config: &MinverConfig,
+) -> Result<Vec<VersionMismatch>> {
+    let manifest_path = Path::new(manifest_dir).join("Cargo.toml");
+    log::debug!("Will check manifest file at {:?}", manifest_path);
+
+    let document = read_manifest(&manifest_path)?;
+    let version = match get_package_version(Path::new(manifest_dir), &document, config)? {
+        Some(version) => version,
+        None => return Ok(vec![]),
+    };
+
+    let (version_path, version_document, table_keys) =
+        read_version_manifest(Path::new(manifest_dir), &manifest_path, &document)?;
+    let version_keys = [table_keys, &["version"]].concat();
+
+    let name = String::from(get_str(&document, &["package", "name"]).unwrap_or_default());
+    let actual = get_str(&version_document, &version_keys).map(String::from);
+    let mut mismatches = vec![];
+    if actual.as_deref() != Some(version.to_string().as_str()) {
+        mismatches.push(VersionMismatch {
+            path: version_path,
+            package: name.clone(),
+            expected: version.to_string(),
+            actual,
+        });
+    }
+
+    let versions = vec![(name, version)].into_iter().collect();
+    mismatches.extend(update_lockfile(Path::new(manifest_dir), &versions, false)?);
+    Ok(mismatches)
+}
+
+/// A version in a manifest or lockfile that does not match the calculated version.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VersionMismatch {
+    /// The path of the `Cargo.toml` or `Cargo.lock`.
+    pub path: PathBuf,
+    /// The name of the package.
+    pub package: String,
+    /// The calculated version.
+    pub expected: String,
+    /// The version in the file, or `None` if it is not set.
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for VersionMismatch {
+    /// Formats the mismatch as a diff, e.g. `-version = "0.0.0"` followed by
+    /// `+version = "1.2.3"`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} ({})", self.path.display(), self.package)?;
+        match &self.actual {
+            Some(actual) => writeln!(f, "-version = \"{}\"", actual)?,
+            None => writeln!(f, "-version (not set)")?,
+        }
+        write!(f, "+version = \"{}\"", self.expected)
+    }
+}
+
+/// Updates the versions of every member of the workspace whose root manifest is in
+/// `workspace_dir`.
+/// 
+/// The version is calculated once for the workspace and written to `[workspace.package]` if any
+/// member inherits its version from the workspace, and to the `[package]` of every other member. If
+/// [`MinverConfig::package_scoped`] is set, members with their own version are versioned
+/// separately instead. The version requirements of path dependencies between members, including
+/// those in `[workspace.dependencies]`, are set to the new versions so that `cargo package`
+/// accepts them, and the versions of the members are updated in `Cargo.lock`.
+pub fn update_workspace_version(workspace_dir: &OsString, config: &MinverConfig) -> Result<()> {
+    let workspace_dir = Path::new(workspace_dir);
+    let root_path = workspace_dir.join("Cargo.toml");
+    let mut root = parse_manifest(&root_path)?;
+    if !matches!(root.get("workspace"), Some(workspace) if workspace.is_table_like()) {
+        return Err(MinverError::MissingWorkspaceTable { path: root_path });
+    }
+
+    let workspace_config = MinverConfig {
+        package_scoped: false,
+        ..config.clone()
+    };
+    let workspace_version = match get_package_version(workspace_dir, &root, &workspace_config)? {
+        Some(version) => version,
+        None => return Ok(()),
+    };
+    log::debug!("Updating workspace to version {}", workspace_version);
+
+    let mut members = vec![];
+    for member_dir in find_workspace_members(workspace_dir, &root)? {
+        let path = member_dir.join("Cargo.toml");
+        let document = read_manifest(&path)?;
+        members.push((member_dir, path, document));
+    }
+    let root_is_package = matches!(root.get("package"), Some(package) if package.is_table_like());
 
-    match Repository::open(manifest_dir) {
+    // Versions of the members by package name, used to update dependency requirements
+    let mut versions = HashMap::new();
+    let mut inherits_version = false;
+    for (member_dir, document) in members
+        .iter()
+        .map(|(dir, _, document)| (dir.as_path(), document))
+        .chain(root_is_package.then_some((workspace_dir, &root)))
+    {
+        let version = if is_inherited(document) {
+            inherits_version = true;
+            workspace_version.clone()
+        } else if config.package_scoped {
+            get_package_version(member_dir, document, config)?
+                .unwrap_or_else(|| workspace_version.clone())
+        } else {
+            workspace_version.clone()
+        };
+        if let Some(name) = get_str(document, &["package", "name"]) {
+            versions.insert(String::from(name), version);
+        }
+    }
+
+    for (_, path, document) in &mut members {
+        update_member_versions(document, &versions);
+        write_manifest(path, document)?;
+    }
+
+    if inherits_version || get_str(&root, &["workspace", "package", "version"]).is_some() {
+        let version = workspace_version.to_string();
+        set_value(&mut root["workspace"]["package"], "version", &version);
+    }
+    if let Some(dependencies) = get_table_mut(&mut root, &["workspace", "dependencies"]) {
+        update_dependency_requirements(dependencies, &versions);
+    }
+    if root_is_package {
+        update_member_versions(&mut root, &versions);
+    }
+    write_manifest(&root_path, &root)?;
+    update_lockfile(workspace_dir, &versions, true)?;
+    Ok(())
+}
+
+/// Gets the directories of the members listed in `[workspace] members`, excluding those in
+/// `[workspace] exclude` and the root package. The `*` and `?` wildcards are supported.
+fn find_workspace_members(workspace_dir: &Path, root: &Document) -> Result<Vec<PathBuf>> {
+    let expand = |key: &str| -> Result<Vec<PathBuf>> {
+        let mut dirs = vec![];
+        if let Some(patterns) = get_item(root, &["workspace", key]).and_then(Item::as_array) {
+            for pattern in patterns.iter().filter_map(|pattern| pattern.as_str()) {
+                dirs.extend(expand_member_pattern(workspace_dir, pattern)?);
+            }
+        }
+        Ok(dirs)
+    };
+
+    let excluded = expand("exclude")?;
+    let mut members = vec![];
+    for dir in expand("members")? {
+        let is_root = dir.components().eq(workspace_dir.components());
+        if !is_root && !excluded.contains(&dir) && !members.contains(&dir) {
+            members.push(dir);
+        }
+    }
+    log::debug!("Found workspace members {:?}", members);
+    Ok(members)
+}
+
+/// Gets the directories matching a member pattern that contain a manifest.
+fn expand_member_pattern(workspace_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![workspace_dir.to_path_buf()];
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !component.contains(['*', '?']) {
+            dirs = dirs.into_iter().map(|dir| dir.join(component)).collect();
+            continue;
+        }
+
+        let expression: String = component
+            .chars()
+            .map(|c| match c {
+                '*' => String::from(".*"),
+                '?' => String::from("."),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect();
+        let re =
+            Regex::new(&format!("^{}$", expression)).map_err(|e| MinverError::InvalidConfig {
+                option: String::from("workspace.members"),
+                message: format!("{} ({})", pattern, e),
+            })?;
+
+        let mut matches = vec![];
+        for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+            let entries = fs::read_dir(dir).map_err(|source| MinverError::Io {
+                path: dir.clone(),
+                source,
+            })?;
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                if entry.path().is_dir() && re.is_match(&entry.file_name().to_string_lossy()) {
+                    matches.push(entry.path());
+                }
+            }
+        }
+        matches.sort();
+        dirs = matches;
+    }
+
+    Ok(dirs
+        .into_iter()
+        .filter(|dir| dir.join("Cargo.toml").is_file())
+        .collect())
+}
+
+/// Sets the version of the package in `document`, unless it is inherited from the workspace, and
+/// the version requirements of its dependencies on other members.
+fn update_member_versions(document: &mut Document, versions: &HashMap<String, Version>) {
+    let name = get_str(document, &["package", "name"]).map(String::from);
+    if let Some((name, version)) = name.and_then(|name| versions.get_key_value(&name)) {
+        if !is_inherited(document) {
+            set_value(&mut document["package"], "version", &version.to_string());
+            log::debug!("Updated version of {} to {}", name, version);
+        }
+    }
+
+    for key in DEPENDENCY_TABLES {
+        if let Some(dependencies) = get_table_mut(document, &[key]) {
+            update_dependency_requirements(dependencies, versions);
+        }
+    }
+    if let Some(targets) = get_table_mut(document, &["target"]) {
+        for (_, target) in targets.iter_mut() {
+            for key in DEPENDENCY_TABLES {
+                if let Some(dependencies) = target.get_mut(key).and_then(Item::as_table_mut) {
+                    update_dependency_requirements(dependencies, versions);
+                }
+            }
+        }
+    }
+}
+
+/// Sets the version requirement of every path dependency on a workspace member in
+/// `dependencies` to the version of that member.
+fn update_dependency_requirements(dependencies: &mut Table, versions: &HashMap<String, Version>) {
+    for (key, dependency) in dependencies.iter_mut() {
+        if dependency.get("path").and_then(Item::as_str).is_none() {
+            continue;
+        }
+        let name = dependency
+            .get("package")
+            .and_then(Item::as_str)
+            .unwrap_or_else(|| key.get());
+        if let Some(version) = versions.get(name) {
+            // Build metadata is ignored by version requirements
+            let requirement = version.clone().without_metadata().to_string();
+            log::debug!("Updated requirement on {} to {}", name, requirement);
+            set_value(dependency, "version", &requirement);
+        }
+    }
+}
+
+/// Sets `key` in the table or inline table `item` to `new_value`, keeping the formatting of the
+/// existing value.
+fn set_value(item: &mut Item, key: &str, new_value: &str) {
+    let decor = item
+        .get(key)
+        .and_then(Item::as_value)
+        .map(|v| v.decor().clone());
+    match decor {
+        Some(decor) => {
+            let mut new_value = Value::from(new_value);
+            *new_value.decor_mut() = decor;
+            item[key] = Item::Value(new_value);
+        }
+        None => {
+            item[key] = value(new_value);
+            if let Some(table) = item.as_inline_table_mut() {
+                table.fmt();
+            }
+        }
+    }
+}
+
+/// Sets the versions of the local packages in the `Cargo.lock` of the package or workspace in
+/// `dir`, so that `cargo build --locked` does not fail after their versions were updated. Nothing
+/// is changed if there is no `Cargo.lock` in `dir` or its parents. Returns the versions that were
+/// out of date, which are only reported if `write` is `false`.
+fn update_lockfile(
+    dir: &Path,
+    versions: &HashMap<String, Version>,
+    write: bool,
+) -> Result<Vec<VersionMismatch>> {
+    let lockfile_path = match dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+    {
+        Some(path) => path,
+        None => {
+            log::debug!("No Cargo.lock found for {:?}", dir);
+            return Ok(vec![]);
+        }
+    };
+    let mut lockfile = parse_manifest(&lockfile_path)?;
+    let packages = match lockfile
+        .get_mut("package")
+        .and_then(Item::as_array_of_tables_mut)
+    {
+        Some(packages) => packages,
+        None => return Ok(vec![]),
+    };
+
+    let mut mismatches = vec![];
+    for index in 0..packages.len() {
+        let package = packages.get_mut(index).unwrap();
+        let name = package.get("name").and_then(Item::as_str);
+        let name = String::from(name.unwrap_or_default());
+        let old_version = package.get("version").and_then(Item::as_str);
+        let old_version = String::from(old_version.unwrap_or_default());
+        // Packages from registries and git repositories have a source, local packages do not
+        if let (Some(version), false) = (versions.get(&name), package.contains_key("source")) {
+            let version = version.to_string();
+            if version != old_version {
+                log::debug!(
+                    "Version of {} in Cargo.lock is {} rather than {}",
+                    name,
+                    old_version,
+                    version
+                );
+                if write {
+                    package["version"] = value(version.as_str());
+                }
+                mismatches.push(VersionMismatch {
+                    path: lockfile_path.clone(),
+                    package: name,
+                    expected: version,
+                    actual: Some(old_version),
+                });
+            }
+        }
+    }
+    if mismatches.is_empty() {
+        log::debug!("{:?} is up to date", lockfile_path);
+    }
+    if mismatches.is_empty() || !write {
+        return Ok(mismatches);
+    }
+
+    // Dependencies on packages are recorded as `<name> <version>` if the name is ambiguous
+    let replacements: HashMap<String, String> = mismatches
+        .iter()
+        .map(|m| {
+            let old_version = m.actual.as_deref().unwrap_or_default();
+            (
+                format!("{} {}", m.package, old_version),
+                format!("{} {}", m.package, m.expected),
+            )
+        })
+        .collect();
+    for index in 0..packages.len() {
+        let package = packages.get_mut(index).unwrap();
+        if let Some(dependencies) = package.get_mut("dependencies").and_then(Item::as_array_mut) {
+            for index in 0..dependencies.len() {
+                let dependency = dependencies.get(index).and_then(|d| d.as_str());
+                if let Some(replacement) = dependency.and_then(|d| replacements.get(d)) {
+                    let _ = dependencies.replace(index, replacement.as_str());
+                }
+            }
+        }
+    }
+    log::debug!("Updated {:?}", lockfile_path);
+    write_manifest(&lockfile_path, &lockfile)?;
+    Ok(mismatches)
+}
+
+/// Reads the manifest that sets the version of the package in `manifest_dir`, which is the root
+/// manifest of its workspace if the package inherits its version. Returns the path of the
+/// manifest, the manifest and the keys of the table that contains the version.
+fn read_version_manifest(
+    manifest_dir: &Path,
+    manifest_path: &Path,
+    document: &Document,
+) -> Result<(PathBuf, Document, &'static [&'static str])> {
+    if !is_inherited(document) {
+        return Ok((manifest_path.to_path_buf(), document.clone(), &["package"]));
+    }
+    // Like cargo, use the closest manifest with a `[workspace]` table as the root
+    for dir in manifest_dir.ancestors() {
+        let root_path = dir.join("Cargo.toml");
+        if !root_path.is_file() {
+            continue;
+        }
+        let root = parse_manifest(&root_path)?;
+        if matches!(root.get("workspace"), Some(workspace) if workspace.is_table_like()) {
+            log::debug!("Version is inherited from workspace in {:?}", root_path);
+            return Ok((root_path, root, &["workspace", "package"]));
+        }
+    }
+    Err(MinverError::MissingWorkspaceTable {
+        path: manifest_path.to_path_buf(),
+    })
+}
+
+/// Returns `true` if the package in `document` inherits its version from the workspace, with
+/// either `version.workspace = true` or `version = { workspace = true }`.
+fn is_inherited(document: &Document) -> bool {
+    let inherited = get_item(document, &["package", "version", "workspace"]);
+    inherited.and_then(Item::as_bool) == Some(true)
+}
+
+/// Gets the item at `keys` in `document`, or `None` if any of the keys is missing.
+fn get_item<'a>(document: &'a Document, keys: &[&str]) -> Option<&'a Item> {
+    keys.iter()
+        .try_fold(document.as_item(), |item, key| item.get(key))
+}
+
+/// Gets the string at `keys` in `document`, or `None` if it is missing or not a string.
+fn get_str<'a>(document: &'a Document, keys: &[&str]) -> Option<&'a str> {
+    get_item(document, keys).and_then(Item::as_str)
+}
+
+/// Gets the table at `keys` in `document`, or `None` if it is missing or not a table.
+fn get_table_mut<'a>(document: &'a mut Document, keys: &[&str]) -> Option<&'a mut Table> {
+    keys.iter()
+        .try_fold(document.as_item_mut(), |item, key| item.get_mut(key))
+        .and_then(Item::as_table_mut)
+}
+
+fn write_manifest(manifest_path: &Path, document: &Document) -> Result<()> {
+    fs::write(manifest_path, document.to_string()).map_err(|source| MinverError::Io {
+        path: manifest_path.to_path_buf(),
+        source,
+    })
+}
+
+/// Reads the manifest at `manifest_path`, which must have a `package` table.
+fn read_manifest(manifest_path: &Path) -> Result<Document> {
+    let document = parse_manifest(manifest_path)?;
+    if !matches!(document.get("package"), Some(package) if package.is_table_like()) {
+        return Err(MinverError::MissingPackageTable {
+            path: manifest_path.to_path_buf(),
+        });
+    }
+    Ok(document)
+}
+
+fn parse_manifest(manifest_path: &Path) -> Result<Document> {
+    let document = fs::read_to_string(manifest_path)
+        .map_err(|source| MinverError::Io {
+            path: manifest_path.to_path_buf(),
+            source,
+        })?
+        .parse::<Document>()
+        .map_err(|source| MinverError::InvalidManifest {
+            path: manifest_path.to_path_buf(),
+            source,
+        })?;
+    log::debug!("Successfully read manifest file {:?}", manifest_path);
+    Ok(document)
+}
+
+/// Gets the version of the package, or `None` if the manifest should not be updated.
+fn get_package_version(
+    manifest_dir: &Path,
+    document: &Document,
+    config: &MinverConfig,
+) -> Result<Option<Version>> {
+    let build_info = get_package_build_info(manifest_dir, document, config)?;
+    Ok(build_info.map(|build_info| build_info.version))
+}
+
+/// Gets the version of the package from the first [`crate::VersionSource`] that has one, or `None`
+/// if the version in the manifest should be kept because the crate is packaged or no source has
+/// a version.
+fn get_package_build_info(
+    manifest_dir: &Path,
+    document: &Document,
+    config: &MinverConfig,
+) -> Result<Option<BuildInfo>> {
+    if let Some(version) = &config.version_override {
+        log::info!("Using version override {}", version);
+        return Ok(Some(BuildInfo::without_commit(version.clone())));
+    }
+
+    if version_source::read_cargo_package_version(manifest_dir)?.is_some() {
+        log::info!("Building packaged crate, manifest file is already versioned");
+        return Ok(None);
+    }
+
+    match crate::open_repository(manifest_dir, config) {
         Ok(repo) => {
-            let version = crate::get_version(&repo, config)?;
+            let config = if config.package_scoped {
+                package_scoped_config(&repo, manifest_dir, document, config)?
+            } else {
+                config.clone()
+            };
+            let check_dirty = config.dirty_marker.is_some();
+            let calculation = minver_core::calculate_head_version(&repo, check_dirty, &config)?;
+            let commit = repo.head()?.peel_to_commit()?;
+            Ok(Some(BuildInfo {
+                version: calculation.version,
+                height: calculation.height,
+                commit: Some(commit.id().to_string()),
+                commit_timestamp: Some(commit.time().seconds()),
+            }))
+        }
+        Err(MinverError::RepositoryNotFound { .. }) => {
+            match version_source::read_git_archive_version(manifest_dir, config)? {
+                Some(version) => Ok(Some(BuildInfo::without_commit(version))),
+                None => {
+                    // If we're not being built from our repo, the version doesn't need to be set
+                    log::info!("Build util run outside of repository, using manifest version");
+                    Ok(None)
+                }
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
 
-            document["package"]["version"] = value(version.to_string());
-            log::debug!("Updated version to {}", version);
+/// Defaults `path_filter` to the package directory and `tag_prefix` to `<package name>-v`, unless
+/// they have been configured explicitly.
+fn package_scoped_config(
+    repo: &Repository,
+    manifest_dir: &Path,
+    document: &Document,
+    config: &MinverConfig,
+) -> Result<MinverConfig> {
+    let mut scoped_config = config.clone();
 
-            Ok(fs::write(
-                &manifest_path,
-                document.to_string_in_original_order(),
-            )?)
+    if scoped_config.path_filter.is_empty() {
+        if let Some(workdir) = repo.workdir() {
+            let canonicalize = |path: &Path| {
+                path.canonicalize().map_err(|source| MinverError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            };
+            let package_dir = canonicalize(manifest_dir)?;
+            if let Ok(relative) = package_dir.strip_prefix(canonicalize(workdir)?) {
+                // Pathspecs always use forward slashes, regardless of platform
+                let components: Vec<String> = relative
+                    .components()
+                    .filter_map(|c| match c {
+                        Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                        _ => None,
+                    })
+                    .collect();
+                if !components.is_empty() {
+                    scoped_config.path_filter = vec![components.join("/")];
+                }
+            }
         }
-        Err(_) => {
-            // If we're not being built from our repo, the version doesn't need to be set
-            log::info!("Build util run outside of repository, manifest file will not be updated");
-            Ok(())
+    }
+
+    if scoped_config.tag_prefix.is_empty() && scoped_config.tag_pattern.is_empty() {
+        if let Some(name) = get_str(document, &["package", "name"]) {
+            scoped_config.tag_prefix = format!("{}-v", name);
         }
     }
+
+    log::debug!(
+        "Using package scoped path filter {:?} and tag prefix {}",
+        scoped_config.path_filter,
+        scoped_config.tag_prefix
+    );
+    Ok(scoped_config)
 }
@@ -0,0 +1,339 @@
+use std::env;
+
+use git2::{Commit, Oid, Repository};
+
+use crate::error::Result;
+use crate::minver_core::{self, TagIndex, VersionTag};
+use crate::{MinverConfig, Version};
+
+/// A CI service that exposes the tag that triggered a build through environment variables.
+trait CiEnvironment {
+    /// The name of the CI service, used for logging.
+    fn name(&self) -> &'static str;
+
+    /// Returns the name of the tag and the id of the commit that triggered the build, or `None`
+    /// if the build was not triggered by a tag on this CI service.
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)>;
+}
+
+struct GitHubActions;
+struct GitLabCi;
+struct Buildkite;
+struct CircleCi;
+struct TravisCi;
+
+/// The CI services that are checked, in order. Supporting another service only requires adding it
+/// here.
+const CI_ENVIRONMENTS: &[&dyn CiEnvironment] =
+    &[&GitHubActions, &GitLabCi, &Buildkite, &CircleCi, &TravisCi];
+
+impl CiEnvironment for GitHubActions {
+    fn name(&self) -> &'static str {
+        "GitHub Actions"
+    }
+
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)> {
+        let name = match var("GITHUB_REF") {
+            Some(reference) => reference.strip_prefix("refs/tags/").map(String::from),
+            None if var("GITHUB_REF_TYPE").as_deref() == Some("tag") => var("GITHUB_REF_NAME"),
+            None => None,
+        }?;
+        Some((name, var("GITHUB_SHA")?))
+    }
+}
+
+impl CiEnvironment for GitLabCi {
+    fn name(&self) -> &'static str {
+        "GitLab CI"
+    }
+
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)> {
+        Some((var("CI_COMMIT_TAG")?, var("CI_COMMIT_SHA")?))
+    }
+}
+
+impl CiEnvironment for Buildkite {
+    fn name(&self) -> &'static str {
+        "Buildkite"
+    }
+
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)> {
+        Some((var("BUILDKITE_TAG")?, var("BUILDKITE_COMMIT")?))
+    }
+}
+
+impl CiEnvironment for CircleCi {
+    fn name(&self) -> &'static str {
+        "CircleCI"
+    }
+
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)> {
+        Some((var("CIRCLE_TAG")?, var("CIRCLE_SHA1")?))
+    }
+}
+
+impl CiEnvironment for TravisCi {
+    fn name(&self) -> &'static str {
+        "Travis CI"
+    }
+
+    fn tag(&self, var: &dyn Fn(&str) -> Option<String>) -> Option<(String, String)> {
+        Some((var("TRAVIS_TAG")?, var("TRAVIS_COMMIT")?))
+    }
+}
+
+/// Adds the tag that triggered the current CI build to `tags` if `commit` is `HEAD` and is the
+/// commit that the tag points to, unless the tag was fetched along with the repository or
+/// [`MinverConfig::ci_tag_detection`] is disabled. This lets tag builds use the tagged version
+/// even if the CI service did not fetch any tags, without affecting the versions of other commits.
+pub(crate) fn add_ci_tag(
+    repository: &Repository,
+    commit: &Commit,
+    tags: &mut TagIndex,
+    config: &MinverConfig,
+) -> Result<()> {
+    add_tag(repository, commit, tags, config, &|name| {
+        env::var(name).ok()
+    })
+}
+
+fn add_tag(
+    repository: &Repository,
+    commit: &Commit,
+    tags: &mut TagIndex,
+    config: &MinverConfig,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<()> {
+    if !config.ci_tag_detection {
+        return Ok(());
+    }
+
+    let tag = match detect_tag(var, config) {
+        Some(tag) => tag,
+        None => return Ok(()),
+    };
+    if tag.commit != commit.id() || minver_core::get_head(repository)?.id() != commit.id() {
+        log::debug!(
+            "CI tag {} does not point to HEAD commit {}, ignoring it",
+            tag.name,
+            commit.id()
+        );
+        return Ok(());
+    }
+    if repository
+        .find_reference(&format!("refs/tags/{}", tag.name))
+        .is_ok()
+    {
+        log::debug!("CI tag {} exists in the repository, ignoring it", tag.name);
+        return Ok(());
+    }
+
+    let key = tag.commit.to_string();
+    match tags.get_mut(&key) {
+        Some(commit_tags) => {
+            commit_tags.push(tag);
+            // Sort the same way as the tags read from the repository
+            commit_tags.sort_by(|t1, t2| {
+                t2.version
+                    .cmp_precedence(&t1.version)
+                    .then_with(|| t1.name.cmp(&t2.name))
+            });
+        }
+        None => {
+            tags.insert(key, vec![tag]);
+        }
+    }
+    Ok(())
+}
+
+fn detect_tag(var: &dyn Fn(&str) -> Option<String>, config: &MinverConfig) -> Option<VersionTag> {
+    let (environment, (name, sha)) = CI_ENVIRONMENTS
+        .iter()
+        .find_map(|environment| environment.tag(var).map(|tag| (environment, tag)))?;
+    log::debug!("Found tag {} for {} from {}", name, sha, environment.name());
+
+    let commit = match Oid::from_str(&sha) {
+        Ok(commit) => commit,
+        Err(_) => {
+            log::warn!("Ignoring CI tag {} with invalid commit id {}", name, sha);
+            return None;
+        }
+    };
+    let version = match config
+        .tag_patterns()
+        .iter()
+        .find_map(|pattern| pattern.extract_version(&name))
+        .map(Version::parse)
+    {
+        Some(Ok(version)) => version,
+        _ => {
+            log::debug!("Ignoring CI tag that is not a version tag: {}", name);
+            return None;
+        }
+    };
+
+    log::info!("Using tag {} from {}", name, environment.name());
+    Some(VersionTag {
+        name,
+        version,
+        commit,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use git2::Signature;
+    use tempfile::TempDir;
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn detect(vars: &[(&str, &str)], config: &MinverConfig) -> Option<VersionTag> {
+        let vars: HashMap<&str, &str> = vars.iter().cloned().collect();
+        detect_tag(&|name| vars.get(name).map(|v| String::from(*v)), config)
+    }
+
+    fn commit_on_head(repository: &Repository, message: &str) -> Oid {
+        let signature = Signature::now("testName", "test@example.com").unwrap();
+        let tree_id = repository.index().unwrap().write_tree().unwrap();
+        let tree = repository.find_tree(tree_id).unwrap();
+        let parent = repository
+            .head()
+            .ok()
+            .map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<&Commit> = parent.iter().collect();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+
+    /// Gets the names of the tags of `commit` after adding the tag of a GitHub Actions build of
+    /// `sha`.
+    fn add_github_tag(
+        repository: &Repository,
+        commit: Oid,
+        sha: Oid,
+        config: &MinverConfig,
+    ) -> Vec<String> {
+        let sha = sha.to_string();
+        let vars: HashMap<&str, &str> = [("GITHUB_REF", "refs/tags/5.0.0"), ("GITHUB_SHA", &sha)]
+            .iter()
+            .cloned()
+            .collect();
+        let var = |name: &str| vars.get(name).map(|v| String::from(*v));
+        let mut tags = TagIndex::new();
+        let commit = repository.find_commit(commit).unwrap();
+        add_tag(repository, &commit, &mut tags, config, &var).unwrap();
+        match tags.get(&commit.id().to_string()) {
+            Some(tags) => tags.iter().map(|tag| tag.name.clone()).collect(),
+            None => vec![],
+        }
+    }
+
+    #[test]
+    fn test_detect_github_actions_tag() {
+        let config = MinverConfig::default();
+
+        let tag = detect(
+            &[("GITHUB_REF", "refs/tags/1.2.3"), ("GITHUB_SHA", SHA)],
+            &config,
+        )
+        .unwrap();
+        assert_eq!("1.2.3", tag.name);
+        assert_eq!(Oid::from_str(SHA).unwrap(), tag.commit);
+
+        let tag = detect(
+            &[
+                ("GITHUB_REF_TYPE", "tag"),
+                ("GITHUB_REF_NAME", "1.2.3"),
+                ("GITHUB_SHA", SHA),
+            ],
+            &config,
+        );
+        assert_eq!("1.2.3", tag.unwrap().name);
+
+        assert!(detect(
+            &[("GITHUB_REF", "refs/heads/main"), ("GITHUB_SHA", SHA)],
+            &config
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_detect_other_ci_tags() {
+        let config = MinverConfig::default();
+
+        for vars in &[
+            [("CI_COMMIT_TAG", "1.2.3"), ("CI_COMMIT_SHA", SHA)],
+            [("BUILDKITE_TAG", "1.2.3"), ("BUILDKITE_COMMIT", SHA)],
+            [("CIRCLE_TAG", "1.2.3"), ("CIRCLE_SHA1", SHA)],
+            [("TRAVIS_TAG", "1.2.3"), ("TRAVIS_COMMIT", SHA)],
+        ] {
+            assert_eq!("1.2.3", detect(vars, &config).unwrap().name);
+        }
+    }
+
+    #[test]
+    fn test_ci_tag_must_match_tag_pattern() {
+        let config = MinverConfig {
+            tag_prefix: String::from("v"),
+            ..MinverConfig::default()
+        };
+
+        let vars = [("CI_COMMIT_TAG", "v1.2.3"), ("CI_COMMIT_SHA", SHA)];
+        assert_eq!(
+            Version::parse("1.2.3").unwrap(),
+            detect(&vars, &config).unwrap().version
+        );
+        assert!(detect(
+            &[("CI_COMMIT_TAG", "1.2.3"), ("CI_COMMIT_SHA", SHA)],
+            &config
+        )
+        .is_none());
+        assert!(detect(
+            &[("CI_COMMIT_TAG", "vnext"), ("CI_COMMIT_SHA", SHA)],
+            &config
+        )
+        .is_none());
+        assert!(detect(&[("CI_COMMIT_TAG", "v1.2.3")], &config).is_none());
+    }
+
+    #[test]
+    fn test_ci_tag_is_only_added_to_head() {
+        let dir = TempDir::new().unwrap();
+        let repository = Repository::init(dir.path()).unwrap();
+        let previous = commit_on_head(&repository, "m1");
+        let head = commit_on_head(&repository, "m2");
+        let config = MinverConfig::default();
+
+        assert_eq!(
+            vec!["5.0.0"],
+            add_github_tag(&repository, head, head, &config)
+        );
+        assert!(add_github_tag(&repository, head, previous, &config).is_empty());
+        // The tag of an earlier build does not change the version of that commit either
+        assert!(add_github_tag(&repository, previous, previous, &config).is_empty());
+
+        let disabled = MinverConfig {
+            ci_tag_detection: false,
+            ..MinverConfig::default()
+        };
+        assert!(add_github_tag(&repository, head, head, &disabled).is_empty());
+
+        let head_object = repository.find_object(head, None).unwrap();
+        repository
+            .tag_lightweight("5.0.0", &head_object, false)
+            .unwrap();
+        assert!(add_github_tag(&repository, head, head, &config).is_empty());
+    }
+}
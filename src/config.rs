@@ -1,27 +1,160 @@
+use crate::branch_rule::BranchRule;
+use crate::conventional::{self, CommitTypeLevel, IncrementLevel};
+use crate::dirty::DirtyMarker;
+use crate::error::{MinverError, Result};
+use crate::height::HeightMode;
+use crate::metadata;
 use crate::semver::Level as SemVerLevel;
-use anyhow::Result;
+use crate::semver::MajorMinor;
+use crate::shallow::ShallowPolicy;
+use crate::tag_pattern::TagPattern;
+use crate::Version;
 use config::{Config, ConfigError, Environment};
 use log::Level as LogLevel;
 use regex::Regex;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// The prefix of the environment variables read by [`MinverConfig::read_from_env`].
+const ENV_PREFIX: &str = "MINVER";
+/// The options that can be set with environment variables, in the order of [`MinverConfig`].
+const OPTIONS: &[&str] = &[
+    "log_level",
+    "auto_increment_level",
+    "build_metadata",
+    "prerelease_identifier",
+    "tag_prefix",
+    "tag_pattern",
+    "minimum_major_minor",
+    "ignore_height",
+    "discover_repository",
+    "path_filter",
+    "package_scoped",
+    "first_parent",
+    "height_mode",
+    "shallow_policy",
+    "ci_tag_detection",
+    "version_override",
+    "dirty_marker",
+    "dirty_ignore_untracked",
+    "branch_rules",
+    "commit_type_levels",
+    "auto_initial_development",
+    "bump_trailers",
+];
 
 const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Warn;
-const DEFAULT_INCREMENT_LEVEL: SemVerLevel = SemVerLevel::Patch;
+const DEFAULT_INCREMENT_LEVEL: IncrementLevel = IncrementLevel::Fixed(SemVerLevel::Patch);
 const DEFAULT_BUILD_METADATA: Option<String> = None;
 const DEFAULT_PRERELEASE_IDENTIFIER: &str = "alpha";
 const DEFAULT_TAG_PREFIX: &str = "";
+const DEFAULT_MINIMUM_MAJOR_MINOR: Option<MajorMinor> = None;
+const DEFAULT_IGNORE_HEIGHT: bool = false;
+const DEFAULT_DISCOVER_REPOSITORY: bool = true;
+const DEFAULT_PACKAGE_SCOPED: bool = false;
+const DEFAULT_FIRST_PARENT: bool = false;
+const DEFAULT_HEIGHT_MODE: HeightMode = HeightMode::ShortestPath;
+const DEFAULT_SHALLOW_POLICY: ShallowPolicy = ShallowPolicy::Error;
+const DEFAULT_CI_TAG_DETECTION: bool = true;
+const DEFAULT_VERSION_OVERRIDE: Option<Version> = None;
+const DEFAULT_DIRTY_MARKER: Option<DirtyMarker> = None;
+const DEFAULT_DIRTY_IGNORE_UNTRACKED: bool = false;
+const DEFAULT_AUTO_INITIAL_DEVELOPMENT: bool = true;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MinverConfig {
     /// The verbosity of the output (defaults to `WARN`).
     pub log_level: LogLevel,
     /// The semver level that should be incremented on a prerelease version (defaults to `PATCH`).
-    pub auto_increment_level: SemVerLevel,
+    /// If `Auto`, the level is chosen from the messages of the commits since the latest tag
+    /// following the Conventional Commits spec: breaking changes (`!` or a `BREAKING CHANGE:`
+    /// footer) increment the major version and other commits increment the level of their type
+    /// in `commit_type_levels`. If no commit requires an increment, the patch version is
+    /// incremented.
+    pub auto_increment_level: IncrementLevel,
     /// The build metadata that will be appended to the generated version (defaults to `None`).
+    /// The metadata can contain placeholders that are replaced with details of the commit:
+    /// `{sha}`, `{short_sha}`, `{height}`, `{branch}`, `{commit_date}` (as `YYYYMMDD`) and `{tag}`.
+    /// Characters other than ASCII letters, digits and `-` are replaced with `-` in branch and tag
+    /// names. `{branch}` is `detached` if no branch is checked out and `{tag}` is `none` if no tag
+    /// was found.
     pub build_metadata: Option<String>,
     /// The identifier that will be used for prerelease versions (defaults to `alpha`).
     pub prerelease_identifier: String,
     /// Custom prefix that comes before the version in tag names (defaults to an empty string).
+    /// Ignored if `tag_pattern` is set.
     pub tag_prefix: String,
+    /// Patterns describing where the version is located in tag names, e.g. `mycrate@{version}`
+    /// (defaults to no patterns). Multiple patterns can be set by separating them with
+    /// whitespace, in which case the first matching pattern is used. See [`TagPattern`] for the
+    /// supported formats.
+    pub tag_pattern: Vec<TagPattern>,
+    /// The lowest `major.minor` version that will be generated for untagged commits, e.g. `2.0`
+    /// (defaults to `None`).
+    pub minimum_major_minor: Option<MajorMinor>,
+    /// If `true`, the height since the latest tag is ignored and untagged commits use the version
+    /// of the latest tag as-is (defaults to `false`).
+    pub ignore_height: bool,
+    /// If `true`, the repository is searched for in parent directories, so that crates in a
+    /// subdirectory of a repository can be versioned (defaults to `true`).
+    pub discover_repository: bool,
+    /// Paths or globs, relative to the root of the repository, that limit which commits count
+    /// towards the height (defaults to no paths, so every commit is counted). Multiple paths can
+    /// be set by separating them with whitespace. Commits that do not change any of the paths
+    /// are skipped when calculating the height, so a package that has not changed since its
+    /// latest tag keeps the version of that tag.
+    pub path_filter: Vec<String>,
+    /// If `true`, the build util versions each package independently: `path_filter` defaults to
+    /// the directory of the package and `tag_prefix` defaults to `<package name>-v`
+    /// (defaults to `false`).
+    pub package_scoped: bool,
+    /// If `true`, only the first parent of merge commits is followed when searching for tags,
+    /// like `git describe --first-parent`, so tags on merged branches are ignored
+    /// (defaults to `false`).
+    pub first_parent: bool,
+    /// How the height since the latest tag is measured: `shortest_path`, `first_parent` or
+    /// `commit_count` (defaults to `shortest_path`). Unlike `shortest_path`, the other modes
+    /// always increase when a branch is merged. See [`HeightMode`] for details.
+    pub height_mode: HeightMode,
+    /// What happens when the search for version tags reaches the end of the history of a shallow
+    /// clone: `error`, `warn` or `limit` (defaults to `error`). See [`ShallowPolicy`] for
+    /// details.
+    pub shallow_policy: ShallowPolicy,
+    /// If `true`, the tag that triggered a CI build is read from the environment variables of the
+    /// CI service (e.g. `GITHUB_REF` or `CI_COMMIT_TAG`) and used for `HEAD` if `HEAD` is the
+    /// tagged commit and the tag matches the tag pattern, even if the tag was not fetched
+    /// (defaults to `true`).
+    pub ci_tag_detection: bool,
+    /// A version that is used instead of calculating one, e.g. when building outside of a
+    /// repository (defaults to `None`). See [`crate::VersionSource`] for the other sources that
+    /// are used outside of a repository.
+    pub version_override: Option<Version>,
+    /// The marker added to the version of `HEAD` when the index or working tree has uncommitted
+    /// changes, e.g. `+dirty` to append build metadata or `-dirty` to append a prerelease
+    /// identifier (defaults to `None`). See [`DirtyMarker`] for details. Note that a `Cargo.toml`
+    /// updated by the build util counts as an uncommitted change.
+    pub dirty_marker: Option<DirtyMarker>,
+    /// If `true`, untracked files do not make the working tree dirty (defaults to `false`).
+    pub dirty_ignore_untracked: bool,
+    /// Rules that choose the prerelease identifier based on the checked out branch, e.g.
+    /// `main=alpha release/*=rc feature/*=feat-{branch_slug}` (defaults to no rules). Multiple
+    /// rules can be set by separating them with whitespace, in which case the first matching rule
+    /// is used. If no rule matches, `prerelease_identifier` is used. See [`BranchRule`] for the
+    /// supported formats, including the rule for a detached `HEAD`.
+    pub branch_rules: Vec<BranchRule>,
+    /// The levels incremented by Conventional Commits types when `auto_increment_level` is
+    /// `Auto`, e.g. `feat=Minor fix=Patch perf=Patch` (defaults to `feat=Minor fix=Patch`).
+    /// Multiple mappings are separated with whitespace.
+    pub commit_type_levels: Vec<CommitTypeLevel>,
+    /// If `true` and `auto_increment_level` is `Auto`, breaking changes increment the minor
+    /// version rather than the major version while the major version is `0` (defaults to `true`).
+    pub auto_initial_development: bool,
+    /// The keys of commit message trailers that request an increment, e.g. `Version-Bump` for
+    /// `Version-Bump: minor` (defaults to none, which disables trailers). Multiple keys are
+    /// separated with whitespace. Keys are case-insensitive and the values are `major`, `minor` or
+    /// `patch`. The highest level requested by the commits since the latest tag is
+    /// used instead of `auto_increment_level`.
+    pub bump_trailers: Vec<String>,
 }
 
 impl MinverConfig {
@@ -30,27 +163,27 @@ impl MinverConfig {
     /// not set, the default value will be used.
     pub fn read_from_env() -> Result<MinverConfig> {
         let mut settings = Config::default();
-        settings.merge(Environment::with_prefix("MINVER"))?;
+        settings.merge(Environment::with_prefix(ENV_PREFIX))?;
 
         Ok(MinverConfig {
             log_level: match settings.get_str("log_level") {
-                Ok(str) => str.parse()?,
+                Ok(str) => parse_value("log_level", &str)?,
                 Err(_) => DEFAULT_LOG_LEVEL,
             },
             auto_increment_level: match settings.get_str("auto_increment_level") {
-                Ok(str) => str.parse()?,
+                Ok(str) => parse_value("auto_increment_level", &str)?,
                 Err(_) => DEFAULT_INCREMENT_LEVEL,
             },
             build_metadata: match settings.get_str("build_metadata") {
                 Ok(str) => {
-                    check_build_metadata(&str)?;
+                    metadata::check_template(&str)?;
                     Some(str)
                 }
                 Err(_) => DEFAULT_BUILD_METADATA,
             },
             prerelease_identifier: match settings.get_str("prerelease_identifier") {
                 Ok(str) => {
-                    check_prerelease_identifier(&str)?;
+                    check_prerelease_identifier("prerelease_identifier", &str)?;
                     str
                 }
                 Err(_) => String::from(DEFAULT_PRERELEASE_IDENTIFIER),
@@ -58,47 +191,192 @@ impl MinverConfig {
             tag_prefix: settings
                 .get_str("tag_prefix")
                 .unwrap_or(String::from(DEFAULT_TAG_PREFIX)),
+            tag_pattern: match settings.get_str("tag_pattern") {
+                Ok(str) => str
+                    .split_whitespace()
+                    .map(|pattern| parse_value("tag_pattern", pattern))
+                    .collect::<Result<_>>()?,
+                Err(_) => vec![],
+            },
+            minimum_major_minor: match settings.get_str("minimum_major_minor") {
+                Ok(str) => Some(parse_value("minimum_major_minor", &str)?),
+                Err(_) => DEFAULT_MINIMUM_MAJOR_MINOR,
+            },
+            ignore_height: get_bool_or(&settings, "ignore_height", DEFAULT_IGNORE_HEIGHT)?,
+            discover_repository: get_bool_or(
+                &settings,
+                "discover_repository",
+                DEFAULT_DISCOVER_REPOSITORY,
+            )?,
+            path_filter: match settings.get_str("path_filter") {
+                Ok(str) => str.split_whitespace().map(String::from).collect(),
+                Err(_) => vec![],
+            },
+            package_scoped: get_bool_or(&settings, "package_scoped", DEFAULT_PACKAGE_SCOPED)?,
+            first_parent: get_bool_or(&settings, "first_parent", DEFAULT_FIRST_PARENT)?,
+            height_mode: match settings.get_str("height_mode") {
+                Ok(str) => parse_value("height_mode", &str)?,
+                Err(_) => DEFAULT_HEIGHT_MODE,
+            },
+            shallow_policy: match settings.get_str("shallow_policy") {
+                Ok(str) => parse_value("shallow_policy", &str)?,
+                Err(_) => DEFAULT_SHALLOW_POLICY,
+            },
+            ci_tag_detection: get_bool_or(&settings, "ci_tag_detection", DEFAULT_CI_TAG_DETECTION)?,
+            version_override: match settings.get_str("version_override") {
+                Ok(str) => Some(parse_value("version_override", &str)?),
+                Err(_) => DEFAULT_VERSION_OVERRIDE,
+            },
+            dirty_marker: match settings.get_str("dirty_marker") {
+                Ok(str) => Some(parse_value("dirty_marker", &str)?),
+                Err(_) => DEFAULT_DIRTY_MARKER,
+            },
+            dirty_ignore_untracked: get_bool_or(
+                &settings,
+                "dirty_ignore_untracked",
+                DEFAULT_DIRTY_IGNORE_UNTRACKED,
+            )?,
+            branch_rules: match settings.get_str("branch_rules") {
+                Ok(str) => str
+                    .split_whitespace()
+                    .map(|rule| parse_value("branch_rules", rule))
+                    .collect::<Result<_>>()?,
+                Err(_) => vec![],
+            },
+            commit_type_levels: match settings.get_str("commit_type_levels") {
+                Ok(str) => str
+                    .split_whitespace()
+                    .map(|mapping| parse_value("commit_type_levels", mapping))
+                    .collect::<Result<_>>()?,
+                Err(_) => conventional::default_commit_type_levels(),
+            },
+            auto_initial_development: get_bool_or(
+                &settings,
+                "auto_initial_development",
+                DEFAULT_AUTO_INITIAL_DEVELOPMENT,
+            )?,
+            bump_trailers: match settings.get_str("bump_trailers") {
+                Ok(str) => str.split_whitespace().map(String::from).collect(),
+                Err(_) => vec![],
+            },
         })
     }
 
+    /// Gets the names of the environment variables read by [`MinverConfig::read_from_env`], e.g.
+    /// `MINVER_TAG_PREFIX`.
+    pub(crate) fn env_var_names() -> Vec<String> {
+        OPTIONS
+            .iter()
+            .map(|option| format!("{}_{}", ENV_PREFIX, option.to_uppercase()))
+            .collect()
+    }
+
+    /// Gets the patterns used to find versions in tag names. If no `tag_pattern` is configured,
+    /// this is a single pattern matching `tag_prefix` followed by the version.
+    pub fn tag_patterns(&self) -> Vec<TagPattern> {
+        if self.tag_pattern.is_empty() {
+            vec![TagPattern::with_prefix(&self.tag_prefix)]
+        } else {
+            self.tag_pattern.clone()
+        }
+    }
+}
+
+impl Default for MinverConfig {
     /// Creates a `MinverConfig` with default values.
-    pub fn default() -> MinverConfig {
+    fn default() -> MinverConfig {
         MinverConfig {
             log_level: DEFAULT_LOG_LEVEL,
             auto_increment_level: DEFAULT_INCREMENT_LEVEL,
             build_metadata: DEFAULT_BUILD_METADATA,
             prerelease_identifier: String::from(DEFAULT_PRERELEASE_IDENTIFIER),
             tag_prefix: String::from(DEFAULT_TAG_PREFIX),
+            tag_pattern: vec![],
+            minimum_major_minor: DEFAULT_MINIMUM_MAJOR_MINOR,
+            ignore_height: DEFAULT_IGNORE_HEIGHT,
+            discover_repository: DEFAULT_DISCOVER_REPOSITORY,
+            path_filter: vec![],
+            package_scoped: DEFAULT_PACKAGE_SCOPED,
+            first_parent: DEFAULT_FIRST_PARENT,
+            height_mode: DEFAULT_HEIGHT_MODE,
+            shallow_policy: DEFAULT_SHALLOW_POLICY,
+            ci_tag_detection: DEFAULT_CI_TAG_DETECTION,
+            version_override: DEFAULT_VERSION_OVERRIDE,
+            dirty_marker: DEFAULT_DIRTY_MARKER,
+            dirty_ignore_untracked: DEFAULT_DIRTY_IGNORE_UNTRACKED,
+            branch_rules: vec![],
+            commit_type_levels: conventional::default_commit_type_levels(),
+            auto_initial_development: DEFAULT_AUTO_INITIAL_DEVELOPMENT,
+            bump_trailers: vec![],
         }
     }
 }
 
-fn check_build_metadata(metadata: &String) -> Result<(), ConfigError> {
-    // Regex partially taken from https://semver.org/#is-there-a-suggested-regular-expression-regex-to-check-a-semver-string
-    let pattern = "^[0-9a-zA-Z-]+(?:\\.[0-9a-zA-Z-]+)*$";
+fn parse_value<T>(option: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|e| invalid_config(option, e))
+}
 
-    let re = Regex::new(pattern).unwrap();
-    if re.is_match(&metadata) {
-        Ok(())
-    } else {
-        Err(ConfigError::Message(format!(
-            "{} is not valid build metadata",
-            metadata
-        )))
+fn get_bool_or(settings: &Config, key: &str, default: bool) -> Result<bool> {
+    match settings.get_bool(key) {
+        Ok(b) => Ok(b),
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        Err(e) => Err(invalid_config(key, e)),
     }
 }
 
-fn check_prerelease_identifier(identifier: &String) -> Result<(), ConfigError> {
+fn invalid_config(option: &str, message: impl Display) -> MinverError {
+    MinverError::InvalidConfig {
+        option: String::from(option),
+        message: message.to_string(),
+    }
+}
+
+pub(crate) fn check_prerelease_identifier(option: &str, identifier: &str) -> Result<()> {
     // Regex partially taken from https://semver.org/#is-there-a-suggested-regular-expression-regex-to-check-a-semver-string
     let pattern = "^(?:0|[1-9]\\d*|\\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\\.(?:0|[1-9]\\d*|\\d*[a-zA-Z-][0-9a-zA-Z-]*))*$";
 
     let re = Regex::new(pattern).unwrap();
-    if re.is_match(&identifier) {
+    if re.is_match(identifier) {
         Ok(())
     } else {
-        Err(ConfigError::Message(format!(
-            "{} is not a valid prerelease identifier",
-            identifier
-        )))
+        Err(invalid_config(
+            option,
+            format!("{} is not a valid prerelease identifier", identifier),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_invalid_values_name_the_option() {
+        let message = |result: Result<()>| result.unwrap_err().to_string();
+
+        assert_eq!(
+            "Invalid value for auto_increment_level: \
+             Value is not one of Major, Minor, Patch or Auto: Huge",
+            message(parse_value::<IncrementLevel>("auto_increment_level", "Huge").map(drop))
+        );
+        assert_eq!(
+            "Invalid value for dirty_marker: \
+             Value is not a + or - followed by a dot separated identifier: dirty",
+            message(parse_value::<DirtyMarker>("dirty_marker", "dirty").map(drop))
+        );
+        assert_eq!(
+            "Invalid value for branch_rules: \
+             Value is not a valid branch rule: main (must be in the form <pattern>=<identifier>)",
+            message(parse_value::<BranchRule>("branch_rules", "main").map(drop))
+        );
+        assert_eq!(
+            "Invalid value for commit_type_levels: \
+             Value is not in the form <type>=<Major|Minor|Patch>: feat=Huge",
+            message(parse_value::<CommitTypeLevel>("commit_type_levels", "feat=Huge").map(drop))
+        );
     }
 }
@@ -0,0 +1,426 @@
+use std::fmt;
+use std::str::FromStr;
+
+use git2::{Commit, Oid, Repository};
+use regex::Regex;
+
+use crate::error::{MinverError, Result};
+use crate::minver_core::{self, VersionTag};
+use crate::path_filter::PathFilter;
+use crate::shallow;
+use crate::{MinverConfig, SemVerLevel};
+
+/// Matches the header of a Conventional Commits message, e.g. `feat(parser)!: add arrays`.
+const HEADER_PATTERN: &str = "^(?P<type>[a-zA-Z]+)(?:\\([^)]*\\))?(?P<breaking>!)?: ";
+/// Matches a footer that marks a breaking change.
+const BREAKING_FOOTER_PATTERN: &str = "(?m)^BREAKING[ -]CHANGE: ";
+
+/// The level that is incremented on a prerelease version.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IncrementLevel {
+    /// Always increments the given level.
+    Fixed(SemVerLevel),
+    /// Chooses the level based on the Conventional Commits messages since the latest tag. When
+    /// the commits are not available, e.g. in a `git archive` export, this is the same as
+    /// `Patch`.
+    Auto,
+}
+
+impl IncrementLevel {
+    /// Gets the level to increment when the commits are not taken into account.
+    pub(crate) fn level(&self) -> SemVerLevel {
+        match self {
+            IncrementLevel::Fixed(level) => level.clone(),
+            IncrementLevel::Auto => SemVerLevel::Patch,
+        }
+    }
+}
+
+/// Maps a Conventional Commits type to the level it increments, e.g. `feat=Minor`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CommitTypeLevel {
+    /// The commit type, e.g. `feat`.
+    pub commit_type: String,
+    /// The level that commits of this type increment.
+    pub level: SemVerLevel,
+}
+
+/// Gets the default mappings from commit types to levels: `feat=Minor fix=Patch`.
+pub(crate) fn default_commit_type_levels() -> Vec<CommitTypeLevel> {
+    vec![
+        CommitTypeLevel {
+            commit_type: String::from("feat"),
+            level: SemVerLevel::Minor,
+        },
+        CommitTypeLevel {
+            commit_type: String::from("fix"),
+            level: SemVerLevel::Patch,
+        },
+    ]
+}
+
+/// Matches a trailer in the last paragraph of a commit message, e.g. `Version-Bump: minor`.
+const TRAILER_PATTERN: &str = "^(?P<key>[A-Za-z0-9-]+):\\s*(?P<value>\\S+)\\s*$";
+
+/// The level to increment, chosen from the messages of the commits since the base tag.
+#[derive(Debug)]
+pub(crate) struct IncrementResolution {
+    /// The chosen level.
+    pub level: SemVerLevel,
+    /// The commit that required the chosen level, if any.
+    pub commit: Option<Oid>,
+    /// The key of the trailer that requested the chosen level, if any.
+    pub trailer: Option<String>,
+}
+
+/// The highest levels requested by the messages of a set of commits, along with the commits that
+/// requested them.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RequestedLevels {
+    trailer: Option<(SemVerLevel, Oid, String)>,
+    conventional: Option<(SemVerLevel, Oid)>,
+}
+
+impl RequestedLevels {
+    /// Adds the levels requested by `other`. On equal levels, the commits of `self` are kept.
+    pub(crate) fn merge(&mut self, other: &RequestedLevels) {
+        if let Some((level, commit, key)) = &other.trailer {
+            if is_higher(self.trailer.as_ref().map(|(l, _, _)| l), level) {
+                self.trailer = Some((level.clone(), *commit, key.clone()));
+            }
+        }
+        if let Some((level, commit)) = &other.conventional {
+            if is_higher(self.conventional.as_ref().map(|(l, _)| l), level) {
+                self.conventional = Some((level.clone(), *commit));
+            }
+        }
+    }
+}
+
+/// Reads the levels requested by commit messages, following [`MinverConfig::bump_trailers`] and
+/// the Conventional Commits spec if [`MinverConfig::auto_increment_level`] is `Auto`.
+pub(crate) struct LevelScanner<'a> {
+    config: &'a MinverConfig,
+    header: Regex,
+    breaking_footer: Regex,
+    trailer: Regex,
+}
+
+impl<'a> LevelScanner<'a> {
+    pub(crate) fn new(config: &'a MinverConfig) -> LevelScanner<'a> {
+        LevelScanner {
+            config,
+            header: Regex::new(HEADER_PATTERN).unwrap(),
+            breaking_footer: Regex::new(BREAKING_FOOTER_PATTERN).unwrap(),
+            trailer: Regex::new(TRAILER_PATTERN).unwrap(),
+        }
+    }
+
+    fn is_auto(&self) -> bool {
+        self.config.auto_increment_level == IncrementLevel::Auto
+    }
+
+    /// Returns `false` if commit messages cannot change the incremented level.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.is_auto() || !self.config.bump_trailers.is_empty()
+    }
+
+    /// Adds the levels requested by the message of `commit` to `levels`. Levels that are already
+    /// in `levels` are kept if they are at least as high.
+    pub(crate) fn add_commit(&self, levels: &mut RequestedLevels, commit: &Commit) {
+        if !self.is_enabled() {
+            return;
+        }
+        let message = commit.message().unwrap_or_default();
+
+        if let Some((level, key)) =
+            get_trailer_level(message, &self.trailer, &self.config.bump_trailers)
+        {
+            log::debug!("{:?} requests a {} increment with {}", commit, level, key);
+            if is_higher(levels.trailer.as_ref().map(|(l, _, _)| l), &level) {
+                levels.trailer = Some((level, commit.id(), key));
+            }
+        }
+
+        if !self.is_auto() {
+            return;
+        }
+        let level = match self.header.captures(message) {
+            Some(captures) if captures.name("breaking").is_some() => Some(SemVerLevel::Major),
+            _ if self.breaking_footer.is_match(message) => Some(SemVerLevel::Major),
+            Some(captures) => self
+                .config
+                .commit_type_levels
+                .iter()
+                .find(|mapping| mapping.commit_type == captures["type"])
+                .map(|mapping| mapping.level.clone()),
+            None => None,
+        };
+        if let Some(level) = level {
+            log::trace!("{:?} requires a {} increment", commit, level);
+            if is_higher(levels.conventional.as_ref().map(|(l, _)| l), &level) {
+                levels.conventional = Some((level, commit.id()));
+            }
+        }
+    }
+
+    /// Reads the levels requested by the commits that are reachable from `commit` but not from
+    /// `base_tag`, skipping the commits that are excluded by `path_filter`.
+    pub(crate) fn scan(
+        &self,
+        repository: &Repository,
+        commit: &Commit,
+        base_tag: Option<&VersionTag>,
+        path_filter: &PathFilter,
+    ) -> Result<RequestedLevels> {
+        let mut levels = RequestedLevels::default();
+        if !self.is_enabled() {
+            return Ok(levels);
+        }
+
+        let hidden = base_tag.map(|tag| tag.commit);
+        for oid in shallow::walk(repository, commit.id(), hidden, self.config.first_parent)? {
+            let commit = repository.find_commit(oid)?;
+            if path_filter.weight(&commit)? != 0 {
+                self.add_commit(&mut levels, &commit);
+            }
+        }
+        Ok(levels)
+    }
+
+    /// Chooses the level to increment from the levels requested by the commits since `base_tag`.
+    ///
+    /// The highest level requested by a trailer in [`MinverConfig::bump_trailers`], e.g.
+    /// `Version-Bump: minor`, takes precedence. Otherwise, an `Auto` level is resolved using the
+    /// Conventional Commits spec.
+    pub(crate) fn resolve(
+        &self,
+        levels: &RequestedLevels,
+        base_tag: Option<&VersionTag>,
+    ) -> IncrementResolution {
+        let initial_development = base_tag.map(|tag| tag.version.major).unwrap_or(0) == 0;
+        let (level, commit, trailer) = match (&levels.trailer, &levels.conventional) {
+            (Some((level, commit, key)), _) => (level.clone(), Some(*commit), Some(key.clone())),
+            (None, Some((SemVerLevel::Major, commit)))
+                if self.config.auto_initial_development && initial_development =>
+            {
+                log::debug!("Breaking changes before 1.0.0 increment the minor version");
+                (SemVerLevel::Minor, Some(*commit), None)
+            }
+            (None, Some((level, commit))) => (level.clone(), Some(*commit), None),
+            (None, None) => {
+                if self.is_auto() {
+                    log::debug!(
+                        "No commits require an increment, falling back to a patch increment"
+                    );
+                }
+                (self.config.auto_increment_level.level(), None, None)
+            }
+        };
+        if let Some(commit) = commit {
+            log::debug!("Incrementing {} as required by {}", level, commit);
+        }
+
+        IncrementResolution {
+            level,
+            commit,
+            trailer,
+        }
+    }
+}
+
+/// Chooses the level to increment from the messages of the commits since the latest of
+/// `candidates`. See [`LevelScanner::resolve`].
+pub(crate) fn resolve_increment_level(
+    repository: &Repository,
+    commit: &Commit,
+    candidates: &[(VersionTag, u32)],
+    path_filter: &PathFilter,
+    config: &MinverConfig,
+) -> Result<IncrementResolution> {
+    let scanner = LevelScanner::new(config);
+    let base_tag = minver_core::latest_candidate(candidates).map(|(tag, _)| tag);
+    let levels = scanner.scan(repository, commit, base_tag, path_filter)?;
+    Ok(scanner.resolve(&levels, base_tag))
+}
+
+/// Gets the highest level requested by the trailers with one of the given keys in `message`,
+/// along with the key of the trailer that requested it.
+fn get_trailer_level(
+    message: &str,
+    trailer: &Regex,
+    keys: &[String],
+) -> Option<(SemVerLevel, String)> {
+    if keys.is_empty() {
+        return None;
+    }
+
+    // Trailers are in the last paragraph of the message, which cannot be the subject
+    let mut paragraphs = message.trim().rsplitn(2, "\n\n");
+    let trailers = match (paragraphs.next(), paragraphs.next()) {
+        (Some(trailers), Some(_)) => trailers,
+        _ => return None,
+    };
+    let mut highest: Option<(SemVerLevel, String)> = None;
+    for captures in trailers.lines().filter_map(|line| trailer.captures(line)) {
+        let key = match keys
+            .iter()
+            .find(|key| key.eq_ignore_ascii_case(&captures["key"]))
+        {
+            Some(key) => key,
+            None => continue,
+        };
+        let level = match captures["value"].to_ascii_lowercase().as_str() {
+            "major" => SemVerLevel::Major,
+            "minor" => SemVerLevel::Minor,
+            "patch" => SemVerLevel::Patch,
+            value => {
+                log::warn!("Ignoring {} trailer with unknown level {}", key, value);
+                continue;
+            }
+        };
+        if is_higher(highest.as_ref().map(|(l, _)| l), &level) {
+            highest = Some((level, key.clone()));
+        }
+    }
+    highest
+}
+
+/// Returns `true` if `level` is higher than `highest`, or if there is no highest level yet.
+fn is_higher(highest: Option<&SemVerLevel>, level: &SemVerLevel) -> bool {
+    match highest {
+        Some(highest) => rank(level) > rank(highest),
+        None => true,
+    }
+}
+
+fn rank(level: &SemVerLevel) -> u8 {
+    match level {
+        SemVerLevel::Major => 3,
+        SemVerLevel::Minor => 2,
+        SemVerLevel::Patch => 1,
+    }
+}
+
+impl FromStr for CommitTypeLevel {
+    type Err = MinverError;
+
+    fn from_str(mapping: &str) -> Result<CommitTypeLevel> {
+        let invalid = || MinverError::InvalidCommitTypeLevel(String::from(mapping));
+
+        let (commit_type, level) = mapping.split_once('=').ok_or_else(invalid)?;
+        match level.parse() {
+            Err(_) => Err(invalid()),
+            Ok(_) if commit_type.is_empty() => Err(invalid()),
+            Ok(level) => Ok(CommitTypeLevel {
+                commit_type: String::from(commit_type),
+                level,
+            }),
+        }
+    }
+}
+
+impl FromStr for IncrementLevel {
+    type Err = MinverError;
+
+    fn from_str(level: &str) -> Result<IncrementLevel> {
+        match level {
+            "Auto" => Ok(IncrementLevel::Auto),
+            _ => level
+                .parse()
+                .map(IncrementLevel::Fixed)
+                .map_err(|_| MinverError::InvalidIncrementLevel(String::from(level))),
+        }
+    }
+}
+
+impl fmt::Display for IncrementLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncrementLevel::Fixed(level) => write!(f, "{}", level),
+            IncrementLevel::Auto => write!(f, "Auto"),
+        }
+    }
+}
+
+impl fmt::Display for CommitTypeLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.commit_type, self.level)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_commit_type_level() {
+        assert_eq!(
+            CommitTypeLevel {
+                commit_type: String::from("perf"),
+                level: SemVerLevel::Patch,
+            },
+            "perf=Patch".parse().unwrap()
+        );
+        assert!("perf".parse::<CommitTypeLevel>().is_err());
+        assert!("=Patch".parse::<CommitTypeLevel>().is_err());
+        assert!("perf=Auto".parse::<CommitTypeLevel>().is_err());
+        assert!("perf=patch".parse::<CommitTypeLevel>().is_err());
+    }
+
+    #[test]
+    fn test_parse_increment_level() {
+        assert_eq!(IncrementLevel::Auto, "Auto".parse().unwrap());
+        assert_eq!(
+            IncrementLevel::Fixed(SemVerLevel::Minor),
+            "Minor".parse().unwrap()
+        );
+        assert!("auto".parse::<IncrementLevel>().is_err());
+        assert_eq!("Auto", IncrementLevel::Auto.to_string());
+        assert_eq!(
+            "Major",
+            IncrementLevel::Fixed(SemVerLevel::Major).to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_trailer_level() {
+        let keys = vec![String::from("Version-Bump"), String::from("Semver")];
+        let trailer = Regex::new(TRAILER_PATTERN).unwrap();
+        let get_trailer_level = |message, keys| get_trailer_level(message, &trailer, keys);
+
+        assert_eq!(
+            Some((SemVerLevel::Minor, String::from("Version-Bump"))),
+            get_trailer_level("fix: a\n\nbody\n\nversion-bump: Minor\n", &keys)
+        );
+        assert_eq!(
+            Some((SemVerLevel::Major, String::from("Semver"))),
+            get_trailer_level("a\n\nVersion-Bump: patch\nSemver: major", &keys)
+        );
+        assert_eq!(
+            None,
+            get_trailer_level("a\n\nVersion-Bump: minor\n\nbody", &keys)
+        );
+        assert_eq!(None, get_trailer_level("Version-Bump: minor", &keys));
+        assert_eq!(None, get_trailer_level("Version-Bump: minor\n", &keys));
+        assert_eq!(None, get_trailer_level("a\n\nOther: minor", &keys));
+        assert_eq!(None, get_trailer_level("a\n\nSemver: huge", &keys));
+        assert_eq!(None, get_trailer_level("a\n\nSemver: major", &[]));
+    }
+
+    #[test]
+    fn test_header_pattern() {
+        let header = Regex::new(HEADER_PATTERN).unwrap();
+
+        let captures = header.captures("feat(parser)!: add arrays").unwrap();
+        assert_eq!("feat", &captures["type"]);
+        assert!(captures.name("breaking").is_some());
+
+        let captures = header.captures("fix: handle empty input\n\nbody").unwrap();
+        assert_eq!("fix", &captures["type"]);
+        assert!(captures.name("breaking").is_none());
+
+        assert!(header.captures("Merge branch 'main'").is_none());
+        assert!(header.captures("feat add arrays").is_none());
+    }
+}
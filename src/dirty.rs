@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+
+use git2::{Repository, StatusOptions};
+use regex::Regex;
+
+use crate::error::{MinverError, Result};
+use crate::{MinverConfig, Version};
+
+/// Marks the version of a working tree with uncommitted changes.
+///
+/// Parsed from an identifier prefixed with `+`, such as `+dirty`, which is appended to the build
+/// metadata, or with `-`, such as `-dirty`, which is appended to the prerelease identifiers.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DirtyMarker {
+    /// An identifier appended to the build metadata, e.g. `1.2.3+dirty`.
+    BuildMetadata(String),
+    /// An identifier appended to the prerelease identifiers, e.g. `1.2.4-alpha.1.dirty`. Release
+    /// versions are incremented first, e.g. `1.2.3` becomes `1.2.4-dirty`.
+    Prerelease(String),
+}
+
+impl DirtyMarker {
+    /// Applies the marker to `version`.
+    pub(crate) fn apply(&self, version: Version, config: &MinverConfig) -> Version {
+        match self {
+            DirtyMarker::BuildMetadata(identifier) => version.with_appended_metadata(identifier),
+            DirtyMarker::Prerelease(identifier) => match version.prerelease {
+                Some(prerelease) => Version {
+                    prerelease: Some(format!("{}.{}", prerelease, identifier)),
+                    ..version
+                },
+                None => Version {
+                    prerelease: Some(identifier.clone()),
+                    ..version.with_incremented_level(&config.auto_increment_level.level())
+                },
+            },
+        }
+    }
+}
+
+/// Returns `true` if the index or the working tree of `repository` differs from `HEAD`.
+/// Untracked files are ignored if [`MinverConfig::dirty_ignore_untracked`] is set.
+pub(crate) fn is_dirty(repository: &Repository, config: &MinverConfig) -> Result<bool> {
+    if repository.is_bare() {
+        return Ok(false);
+    }
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(!config.dirty_ignore_untracked)
+        .recurse_untracked_dirs(!config.dirty_ignore_untracked)
+        .include_ignored(false);
+    let statuses = repository.statuses(Some(&mut options))?;
+    if let Some(entry) = statuses.iter().next() {
+        log::debug!(
+            "Working tree is dirty: {:?} has status {:?}",
+            entry.path(),
+            entry.status()
+        );
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+impl FromStr for DirtyMarker {
+    type Err = MinverError;
+
+    fn from_str(marker: &str) -> Result<DirtyMarker> {
+        let invalid = || MinverError::InvalidDirtyMarker(String::from(marker));
+
+        let re = Regex::new("^[0-9a-zA-Z-]+(?:\\.[0-9a-zA-Z-]+)*$").unwrap();
+        let (kind, identifier) = marker.split_at(marker.chars().next().map_or(0, char::len_utf8));
+        if !re.is_match(identifier) {
+            return Err(invalid());
+        }
+        match kind {
+            "+" => Ok(DirtyMarker::BuildMetadata(String::from(identifier))),
+            "-" => Ok(DirtyMarker::Prerelease(String::from(identifier))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for DirtyMarker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirtyMarker::BuildMetadata(identifier) => write!(f, "+{}", identifier),
+            DirtyMarker::Prerelease(identifier) => write!(f, "-{}", identifier),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_dirty_marker() {
+        assert_eq!(
+            DirtyMarker::BuildMetadata(String::from("dirty")),
+            "+dirty".parse().unwrap()
+        );
+        assert_eq!(
+            DirtyMarker::Prerelease(String::from("local.changes")),
+            "-local.changes".parse().unwrap()
+        );
+        assert!("dirty".parse::<DirtyMarker>().is_err());
+        assert!("+".parse::<DirtyMarker>().is_err());
+        assert!("+dirty..tree".parse::<DirtyMarker>().is_err());
+        assert!("".parse::<DirtyMarker>().is_err());
+    }
+
+    #[test]
+    fn test_apply_prerelease_marker() {
+        let config = MinverConfig::default();
+        let marker = DirtyMarker::Prerelease(String::from("dirty"));
+
+        assert_eq!(
+            "1.2.4-dirty",
+            marker
+                .apply(Version::parse("1.2.3").unwrap(), &config)
+                .to_string()
+        );
+        assert_eq!(
+            "1.2.4-alpha.1.dirty",
+            marker
+                .apply(Version::parse("1.2.4-alpha.1").unwrap(), &config)
+                .to_string()
+        );
+    }
+}
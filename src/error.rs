@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// A specialized `Result` type for minver_rs operations.
+pub type Result<T> = std::result::Result<T, MinverError>;
+
+/// The errors that can occur while calculating or updating a version.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MinverError {
+    /// No git repository was found at the given path.
+    RepositoryNotFound { path: PathBuf, source: git2::Error },
+    /// `HEAD` does not point to a commit, e.g. because nothing has been committed yet.
+    UnbornHead,
+    /// The given revision could not be resolved to a commit.
+    RevisionNotFound { rev: String, source: git2::Error },
+    /// Any other failure while reading the git repository.
+    Git(git2::Error),
+    /// The configuration could not be loaded.
+    Config(config::ConfigError),
+    /// A configuration value is invalid.
+    InvalidConfig { option: String, message: String },
+    /// A string is not a valid semver 2.0 version.
+    InvalidVersion(String),
+    /// A string is not a valid `major.minor` version.
+    InvalidMajorMinor(String),
+    /// A string is not a valid tag pattern.
+    InvalidTagPattern(String),
+    /// A string is not a valid increment level.
+    InvalidIncrementLevel(String),
+    /// A string is not a valid mapping of a Conventional Commits type to a level.
+    InvalidCommitTypeLevel(String),
+    /// A string is not a valid dirty marker.
+    InvalidDirtyMarker(String),
+    /// A string is not a valid branch rule.
+    InvalidBranchRule(String),
+    /// A manifest file could not be read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// A manifest file is not valid TOML.
+    InvalidManifest {
+        path: PathBuf,
+        source: toml_edit::TomlError,
+    },
+    /// A manifest file does not contain a `[package]` table.
+    MissingPackageTable { path: PathBuf },
+    /// A manifest file does not contain a `[workspace]` table.
+    MissingWorkspaceTable { path: PathBuf },
+    /// The repository is a shallow clone and the search for version tags reached the end of its
+    /// history at `commit`.
+    ShallowRepository { commit: git2::Oid },
+}
+
+impl fmt::Display for MinverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinverError::RepositoryNotFound { path, source } => {
+                write!(f, "No git repository found at {:?}: {}", path, source)
+            }
+            MinverError::UnbornHead => write!(f, "HEAD does not point to a commit"),
+            MinverError::RevisionNotFound { rev, source } => {
+                write!(f, "Revision {} could not be found: {}", rev, source)
+            }
+            MinverError::Git(e) => write!(f, "Git error: {}", e),
+            MinverError::Config(e) => write!(f, "Failed to load configuration: {}", e),
+            MinverError::InvalidConfig { option, message } => {
+                write!(f, "Invalid value for {}: {}", option, message)
+            }
+            MinverError::InvalidVersion(version) => {
+                write!(f, "Version is not a valid semver 2.0 version: {}", version)
+            }
+            MinverError::InvalidMajorMinor(major_minor) => write!(
+                f,
+                "Value is not a valid major.minor version: {}",
+                major_minor
+            ),
+            MinverError::InvalidTagPattern(pattern) => {
+                write!(f, "Value is not a valid tag pattern: {}", pattern)
+            }
+            MinverError::InvalidIncrementLevel(level) => write!(
+                f,
+                "Value is not one of Major, Minor, Patch or Auto: {}",
+                level
+            ),
+            MinverError::InvalidCommitTypeLevel(mapping) => write!(
+                f,
+                "Value is not in the form <type>=<Major|Minor|Patch>: {}",
+                mapping
+            ),
+            MinverError::InvalidDirtyMarker(marker) => write!(
+                f,
+                "Value is not a + or - followed by a dot separated identifier: {}",
+                marker
+            ),
+            MinverError::InvalidBranchRule(rule) => {
+                write!(f, "Value is not a valid branch rule: {}", rule)
+            }
+            MinverError::Io { path, source } => {
+                write!(f, "Failed to access {:?}: {}", path, source)
+            }
+            MinverError::InvalidManifest { path, source } => {
+                write!(f, "Failed to parse {:?}: {}", path, source)
+            }
+            MinverError::MissingPackageTable { path } => {
+                write!(f, "{:?} does not contain a [package] table", path)
+            }
+            MinverError::MissingWorkspaceTable { path } => {
+                write!(f, "{:?} does not contain a [workspace] table", path)
+            }
+            MinverError::ShallowRepository { commit } => write!(
+                f,
+                "Repository is a shallow clone and its history ends at {} before the latest \
+                version tag could be found. Fetch the full history and tags (e.g. \
+                `git fetch --unshallow --tags`, or `fetch-depth: 0` for actions/checkout), or set \
+                MINVER_SHALLOW_POLICY to warn or limit",
+                commit
+            ),
+        }
+    }
+}
+
+impl Error for MinverError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinverError::RepositoryNotFound { source, .. } => Some(source),
+            MinverError::RevisionNotFound { source, .. } => Some(source),
+            MinverError::Git(e) => Some(e),
+            MinverError::Config(e) => Some(e),
+            MinverError::Io { source, .. } => Some(source),
+            MinverError::InvalidManifest { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<git2::Error> for MinverError {
+    fn from(e: git2::Error) -> MinverError {
+        MinverError::Git(e)
+    }
+}
+
+impl From<config::ConfigError> for MinverError {
+    fn from(e: config::ConfigError) -> MinverError {
+        MinverError::Config(e)
+    }
+}
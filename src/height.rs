@@ -0,0 +1,66 @@
+use git2::{Commit, Oid, Repository};
+use strum_macros::{Display, EnumString};
+
+use crate::error::Result;
+use crate::path_filter::PathFilter;
+use crate::shallow;
+
+/// Determines how the height between a commit and a tag is measured.
+///
+/// Every mode only counts commits that change the paths in [`crate::MinverConfig::path_filter`].
+#[derive(EnumString, Display, Debug, PartialEq, Eq, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum HeightMode {
+    /// The number of commits on the shortest path from the commit to the tag.
+    ShortestPath,
+    /// The number of commits on the first parent chain of the commit that are not reachable from
+    /// the tag, like `git rev-list --first-parent --count <tag>..<commit>`.
+    FirstParent,
+    /// The number of commits that are reachable from the commit but not from the tag, like
+    /// `git rev-list --count <tag>..<commit>`.
+    CommitCount,
+}
+
+/// Measures the height of `commit` above `tagged_commit` for the modes that are not calculated by
+/// the tag search itself. Returns `None` for [`HeightMode::ShortestPath`].
+pub(crate) fn measure_height(
+    repository: &Repository,
+    commit: &Commit,
+    tagged_commit: Oid,
+    path_filter: &PathFilter,
+    mode: HeightMode,
+) -> Result<Option<u32>> {
+    let first_parent = match mode {
+        HeightMode::ShortestPath => return Ok(None),
+        HeightMode::FirstParent => true,
+        HeightMode::CommitCount => false,
+    };
+
+    let mut height = 0;
+    for oid in shallow::walk(repository, commit.id(), Some(tagged_commit), first_parent)? {
+        height += path_filter.weight(&repository.find_commit(oid)?)?;
+    }
+
+    log::trace!(
+        "Height of {:?} above {} using {} is {}",
+        commit,
+        tagged_commit,
+        mode,
+        height
+    );
+    Ok(Some(height))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_height_mode() {
+        assert_eq!(Ok(HeightMode::ShortestPath), "shortest_path".parse());
+        assert_eq!(Ok(HeightMode::FirstParent), "first_parent".parse());
+        assert_eq!(Ok(HeightMode::CommitCount), "commit_count".parse());
+        assert!("longest_path".parse::<HeightMode>().is_err());
+        assert_eq!("commit_count", HeightMode::CommitCount.to_string());
+    }
+}
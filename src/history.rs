@@ -0,0 +1,225 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use git2::{Commit, Oid, Repository, RevparseMode};
+
+use crate::conventional::{LevelScanner, RequestedLevels};
+use crate::error::{MinverError, Result};
+use crate::metadata::MetadataContext;
+use crate::minver_core::{self, VersionTag};
+use crate::path_filter::PathFilter;
+use crate::shallow;
+use crate::{MinverConfig, Version};
+
+/// The calculated version of a single commit in a version history.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HistoryEntry {
+    /// The id of the commit.
+    pub commit: Oid,
+    /// The calculated version of the commit.
+    pub version: Version,
+    /// The name of the tag that the version is based on, or `None` if no tag was found.
+    pub base_tag: Option<String>,
+    /// The number of commits between the commit and the base tag.
+    pub height: u32,
+}
+
+/// Calculates the version of every commit in `range`, newest commits first.
+///
+/// `range` is either a single revision, in which case all of its ancestors are included, or a
+/// range in the form `from..to` (see `git help revisions`). If [`MinverConfig::first_parent`] is
+/// set, only commits on the first parent chain are included. The tags of the repository are only
+/// read once and the candidate versions of each commit are derived from those of its parents, so
+/// this is much faster than calling [`crate::get_version_at`] for every commit.
+pub fn get_version_history(
+    repository: &Repository,
+    range: &str,
+    config: &MinverConfig,
+) -> Result<Vec<HistoryEntry>> {
+    log::info!(
+        "Getting version history of {} for {:?}",
+        range,
+        repository.path()
+    );
+    // Like `get_version_at` with a commit id, commits in the history are not on a branch
+    let (branch_config, _) = minver_core::apply_branch_rules(None, config)?;
+    let config = &branch_config;
+
+    let mut tags = minver_core::get_tags(repository, &config.tag_patterns())?;
+    let boundary = shallow::add_boundary_tags(repository, &mut tags, config)?;
+
+    let path_filter = PathFilter::new(repository, &config.path_filter);
+    let scanner = LevelScanner::new(config);
+
+    let (commit, hidden) = if range.contains("..") {
+        let (from, to) = resolve_range(repository, range)?;
+        (to, Some(from))
+    } else {
+        (minver_core::resolve_revision(repository, range)?.id(), None)
+    };
+    let commits = shallow::walk(repository, commit, hidden, config.first_parent)?;
+
+    // Since parents are visited before their children, the candidates of every parent in the
+    // range are already known when a commit is visited. Parents outside of the range fall back to
+    // a regular search of their history. The levels requested by the commits since each candidate
+    // tag are carried forward the same way, keyed by the tagged commit.
+    let mut candidates_by_commit: HashMap<Oid, Vec<(VersionTag, u32)>> = HashMap::new();
+    let mut levels_by_commit: HashMap<Oid, HashMap<Oid, RequestedLevels>> = HashMap::new();
+    let mut entries = vec![];
+
+    for oid in commits {
+        let commit = repository.find_commit(oid)?;
+        log::trace!("Checking {:?}", &commit);
+
+        let (candidates, levels) = match minver_core::get_commit_tags(&tags, commit.id()).first() {
+            Some(tag) => {
+                let levels = HashMap::from([(tag.commit, RequestedLevels::default())]);
+                (vec![(tag.clone(), 0)], levels)
+            }
+            None => {
+                let weight = path_filter.weight(&commit)?;
+                let parents = minver_core::get_parents(&commit, config);
+                let mut closest: HashMap<Oid, (VersionTag, u32)> = HashMap::new();
+                for parent in &parents {
+                    if let Entry::Vacant(entry) = candidates_by_commit.entry(parent.id()) {
+                        log::trace!("Parent is outside of range, searching {:?}", parent);
+                        let parent_candidates =
+                            minver_core::find_latest_versions(&tags, parent, &path_filter, config)?;
+                        levels_by_commit.insert(
+                            parent.id(),
+                            scan_levels(
+                                repository,
+                                parent,
+                                &parent_candidates,
+                                &path_filter,
+                                &scanner,
+                            )?,
+                        );
+                        entry.insert(parent_candidates);
+                    }
+
+                    for (tag, height) in &candidates_by_commit[&parent.id()] {
+                        match closest.get(&tag.commit) {
+                            Some((_, closest_height)) if *closest_height <= height + weight => {}
+                            _ => {
+                                closest.insert(tag.commit, (tag.clone(), height + weight));
+                            }
+                        }
+                    }
+                }
+
+                let mut own_levels = RequestedLevels::default();
+                if weight != 0 {
+                    scanner.add_commit(&mut own_levels, &commit);
+                }
+                let mut levels: HashMap<Oid, RequestedLevels> = if closest.is_empty() {
+                    HashMap::from([(Oid::zero(), own_levels)])
+                } else {
+                    closest
+                        .keys()
+                        .map(|tag_commit| (*tag_commit, own_levels.clone()))
+                        .collect()
+                };
+                for (tag_commit, tag_levels) in levels.iter_mut() {
+                    let tag = closest.get(tag_commit).map(|(tag, _)| tag);
+                    for parent in &parents {
+                        // None of the commits of a parent without tags are reachable from a tag
+                        let parent_levels = &levels_by_commit[&parent.id()];
+                        match parent_levels
+                            .get(tag_commit)
+                            .or_else(|| parent_levels.get(&Oid::zero()))
+                        {
+                            Some(parent_levels) => tag_levels.merge(parent_levels),
+                            None => {
+                                log::trace!("{:?} is not a candidate of {:?}", tag, parent);
+                                let parent_levels =
+                                    scanner.scan(repository, parent, tag, &path_filter)?;
+                                tag_levels.merge(&parent_levels);
+                            }
+                        }
+                    }
+                }
+                (closest.into_values().collect(), levels)
+            }
+        };
+
+        // Heights are propagated using the shortest path, other modes are measured per commit
+        let measured_candidates = minver_core::apply_height_mode(
+            repository,
+            &commit,
+            shallow::apply_shallow_policy(candidates.clone(), &boundary, config)?,
+            &path_filter,
+            config,
+        )?;
+        let context = MetadataContext::new(repository, &commit, None)?;
+        let base_tag = minver_core::latest_candidate(&measured_candidates).map(|(tag, _)| tag);
+        let base_levels = levels.get(&base_tag.map_or(Oid::zero(), |tag| tag.commit));
+        let resolution =
+            scanner.resolve(base_levels.unwrap_or(&RequestedLevels::default()), base_tag);
+        let calculation = minver_core::calculate_from_candidates(
+            measured_candidates,
+            vec![],
+            minver_core::is_tagged(&tags, commit.id()),
+            &context,
+            &resolution.level,
+            config,
+        )?;
+        log::debug!(
+            "Calculated version {} for {:?}",
+            calculation.version,
+            &commit
+        );
+
+        entries.push(HistoryEntry {
+            commit: commit.id(),
+            version: calculation.version,
+            base_tag: calculation.base_tag.map(|tag| tag.name),
+            height: calculation.height,
+        });
+        candidates_by_commit.insert(commit.id(), candidates);
+        levels_by_commit.insert(commit.id(), levels);
+    }
+
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Reads the levels requested by the commits since each of the `candidates` of `commit`, or by
+/// all of its commits if there are no candidates.
+fn scan_levels(
+    repository: &Repository,
+    commit: &Commit,
+    candidates: &[(VersionTag, u32)],
+    path_filter: &PathFilter,
+    scanner: &LevelScanner,
+) -> Result<HashMap<Oid, RequestedLevels>> {
+    if candidates.is_empty() {
+        let levels = scanner.scan(repository, commit, None, path_filter)?;
+        return Ok(HashMap::from([(Oid::zero(), levels)]));
+    }
+    candidates
+        .iter()
+        .map(|(tag, _)| {
+            let levels = scanner.scan(repository, commit, Some(tag), path_filter)?;
+            Ok((tag.commit, levels))
+        })
+        .collect()
+}
+
+/// Resolves the commits at both ends of a `from..to` range.
+fn resolve_range(repository: &Repository, range: &str) -> Result<(Oid, Oid)> {
+    repository
+        .revparse(range)
+        .and_then(|spec| match (spec.from(), spec.to()) {
+            (Some(from), Some(to)) if !spec.mode().contains(RevparseMode::MERGE_BASE) => {
+                Ok((from.peel_to_commit()?.id(), to.peel_to_commit()?.id()))
+            }
+            _ => Err(git2::Error::from_str(
+                "expected a range in the form from..to",
+            )),
+        })
+        .map_err(|source| MinverError::RevisionNotFound {
+            rev: String::from(range),
+            source,
+        })
+}
@@ -5,18 +5,54 @@
 //! latest tag appended to the version.
 //! 
 //! To use this crate, call [`build_utils::default_build_action()`] in `build.rs`, then set
-//! [`build_utils::UPDATE_VERSION_VAR`].
+//! [`build_utils::UPDATE_VERSION_VAR`]. To leave `Cargo.toml` untouched, call
+//! [`build_utils::rustc_env_build_action()`] instead and read the version with
+//! `env!("MINVER_VERSION")`.
 //! 
 //! See [`MinverConfig`] for details on available options.
 //! 
 //! Based on https://github.com/adamralph/minver
 
+mod branch_rule;
 pub mod build_utils;
+mod ci;
 mod config;
+mod conventional;
+mod dirty;
+mod error;
+mod height;
+mod history;
+mod metadata;
 mod minver_core;
+mod path_filter;
 mod semver;
+mod shallow;
+mod tag_pattern;
+mod version_source;
 
 pub use crate::config::MinverConfig;
+pub use branch_rule::BranchRule;
+pub use conventional::CommitTypeLevel;
+pub use conventional::IncrementLevel;
+pub use dirty::DirtyMarker;
+pub use error::MinverError;
+pub use error::Result;
+pub use height::HeightMode;
+pub use history::get_version_history;
+pub use history::HistoryEntry;
 pub use minver_core::get_version;
+pub use minver_core::get_version_at;
+pub use minver_core::get_version_detailed;
+pub use minver_core::get_version_detailed_at;
+pub use minver_core::open_repository;
+pub use minver_core::ConfigInfluence;
 pub use minver_core::Version;
+pub use minver_core::VersionCalculation;
+pub use minver_core::VersionTag;
 pub use semver::Level as SemVerLevel;
+pub use semver::MajorMinor;
+pub use shallow::ShallowPolicy;
+pub use tag_pattern::TagPattern;
+pub use version_source::get_version_from_sources;
+pub use version_source::SourcedVersion;
+pub use version_source::VersionSource;
@@ -0,0 +1,217 @@
+use git2::{Commit, Repository};
+use regex::{Captures, Regex};
+
+use crate::error::{MinverError, Result};
+use crate::minver_core::VersionTag;
+
+/// Matches a placeholder in a build metadata template, e.g. `{short_sha}`.
+const PLACEHOLDER_PATTERN: &str = "\\{([a-z_]*)\\}";
+const PLACEHOLDERS: &[&str] = &["sha", "short_sha", "height", "branch", "commit_date", "tag"];
+/// The value of `{branch}` when no branch is checked out.
+const DETACHED_BRANCH: &str = "detached";
+/// The value of `{tag}` when no tag was found.
+const NO_TAG: &str = "none";
+
+/// The values of the commit-specific build metadata placeholders.
+#[derive(Debug, Default)]
+pub(crate) struct MetadataContext {
+    sha: Option<String>,
+    short_sha: Option<String>,
+    branch: Option<String>,
+    commit_date: Option<String>,
+}
+
+impl MetadataContext {
+    /// Creates the context for `commit`, which is checked out on `branch` if it is `Some`.
+    pub fn new(
+        repository: &Repository,
+        commit: &Commit,
+        branch: Option<&str>,
+    ) -> Result<MetadataContext> {
+        let short_sha = repository
+            .find_object(commit.id(), None)?
+            .short_id()?
+            .as_str()
+            .map(String::from);
+        Ok(MetadataContext {
+            sha: Some(commit.id().to_string()),
+            short_sha,
+            branch: Some(branch.map_or(String::from(DETACHED_BRANCH), slugify)),
+            commit_date: Some(format_date(commit.time().seconds())),
+        })
+    }
+
+    /// Creates a context where only the abbreviated commit id is known, e.g. when the version is
+    /// read from the output of `git describe`.
+    pub fn with_short_sha(short_sha: &str) -> MetadataContext {
+        MetadataContext {
+            short_sha: Some(String::from(short_sha)),
+            ..MetadataContext::default()
+        }
+    }
+}
+
+/// Replaces the placeholders in a build metadata template and checks that the result is valid
+/// build metadata.
+pub(crate) fn expand(
+    template: &str,
+    context: &MetadataContext,
+    height: u32,
+    tag: Option<&VersionTag>,
+) -> Result<String> {
+    let re = Regex::new(PLACEHOLDER_PATTERN).unwrap();
+    let mut missing = None;
+    let metadata = re.replace_all(template, |captures: &Captures| {
+        let value = match &captures[1] {
+            "sha" => context.sha.clone(),
+            "short_sha" => context.short_sha.clone(),
+            "height" => Some(height.to_string()),
+            "branch" => context.branch.clone(),
+            "commit_date" => context.commit_date.clone(),
+            "tag" => Some(tag.map_or(String::from(NO_TAG), |tag| slugify(&tag.name))),
+            _ => None,
+        };
+        value.unwrap_or_else(|| {
+            missing = Some(String::from(&captures[0]));
+            String::new()
+        })
+    });
+
+    if let Some(placeholder) = missing {
+        return Err(invalid_metadata(format!(
+            "{} is not available for this version",
+            placeholder
+        )));
+    }
+    check_metadata(&metadata)?;
+    Ok(metadata.into_owned())
+}
+
+/// Checks that `template` only contains known placeholders and is valid build metadata once they
+/// are replaced.
+pub(crate) fn check_template(template: &str) -> Result<()> {
+    let re = Regex::new(PLACEHOLDER_PATTERN).unwrap();
+    if let Some(captures) = re
+        .captures_iter(template)
+        .find(|captures| !PLACEHOLDERS.contains(&&captures[1]))
+    {
+        return Err(invalid_metadata(format!(
+            "{} is not a known placeholder",
+            &captures[0]
+        )));
+    }
+
+    check_metadata(&re.replace_all(template, "0"))
+}
+
+/// Replaces every character other than ASCII letters and digits with `-`, so that the result is a
+/// valid semver identifier.
+pub(crate) fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn check_metadata(metadata: &str) -> Result<()> {
+    // Regex partially taken from https://semver.org/#is-there-a-suggested-regular-expression-regex-to-check-a-semver-string
+    let re = Regex::new("^[0-9a-zA-Z-]+(?:\\.[0-9a-zA-Z-]+)*$").unwrap();
+    if re.is_match(metadata) {
+        Ok(())
+    } else {
+        Err(invalid_metadata(format!(
+            "{} is not valid build metadata",
+            metadata
+        )))
+    }
+}
+
+fn invalid_metadata(message: String) -> MinverError {
+    MinverError::InvalidConfig {
+        option: String::from("build_metadata"),
+        message,
+    }
+}
+
+/// Formats a unix timestamp as a UTC date in the form `YYYYMMDD`.
+fn format_date(timestamp: i64) -> String {
+    // Converts days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use git2::Oid;
+
+    use crate::Version;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let context = MetadataContext {
+            sha: Some(String::from("0123456789abcdef0123456789abcdef01234567")),
+            short_sha: Some(String::from("0123456")),
+            branch: Some(slugify("feature/my_branch")),
+            commit_date: Some(format_date(1700000000)),
+        };
+        let tag = VersionTag {
+            name: String::from("release/v1.2.3"),
+            version: Version::parse("1.2.3").unwrap(),
+            commit: Oid::zero(),
+        };
+
+        assert_eq!(
+            "feature-my-branch.3.g0123456.20231114.release-v1-2-3",
+            expand(
+                "{branch}.{height}.g{short_sha}.{commit_date}.{tag}",
+                &context,
+                3,
+                Some(&tag)
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "sha.0123456789abcdef0123456789abcdef01234567.none",
+            expand("sha.{sha}.{tag}", &context, 0, None).unwrap()
+        );
+        assert!(expand(
+            "{sha}",
+            &MetadataContext::with_short_sha("0123456"),
+            0,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_template() {
+        assert!(check_template("build.{short_sha}").is_ok());
+        assert!(check_template("{branch}-{height}").is_ok());
+        assert!(check_template("build.{unknown}").is_err());
+        assert!(check_template("build.{short_sha}.").is_err());
+        assert!(check_template("build_1").is_err());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!("19700101", format_date(0));
+        assert_eq!("20000229", format_date(951782400));
+        assert_eq!("20231114", format_date(1700000000));
+    }
+}
@@ -1,3 +1,7 @@
+use std::env;
+use std::ffi::OsString;
+use std::process;
+
 use minver_rs::{build_utils, MinverConfig};
 
 fn main() {
@@ -5,5 +9,36 @@ fn main() {
     if let Err(e) = simple_logger::init_with_level(config.log_level) {
         println!("Failed to initialize log: {}", e);
     }
-    build_utils::default_build_action_silent(&config);
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => build_utils::default_build_action_silent(&config),
+        ["--check"] => check(&config),
+        _ => {
+            println!("Usage: minver_build_util [--check]");
+            process::exit(2);
+        }
+    }
+}
+
+/// Exits with a non-zero status if the versions in `Cargo.toml` or `Cargo.lock` are out of date,
+/// without updating them.
+fn check(config: &MinverConfig) {
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap_or(OsString::from("."));
+    match build_utils::check_package_version(&manifest_dir, config) {
+        Ok(mismatches) if mismatches.is_empty() => println!("Versions are up to date"),
+        Ok(mismatches) => {
+            mismatches.iter().for_each(|m| println!("{}", m));
+            process::exit(1);
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            process::exit(2);
+        }
+    }
 }
@@ -1,10 +1,24 @@
 use std::env;
+use std::process;
 
-use anyhow::Result;
-use git2::Repository;
+use anyhow::{anyhow, Result};
 
-use minver_rs::MinverConfig;
-use minver_rs::Version;
+use minver_rs::{build_utils, HistoryEntry, MinverConfig, VersionCalculation};
+
+/// Environment variable that sets the revision to calculate the version of. The `--rev` argument
+/// takes precedence over this variable.
+const REVISION_VAR: &str = "MINVER_REVISION";
+
+enum Command {
+    /// Prints the version of a single revision.
+    Version { rev: Option<String> },
+    /// Prints the version of every commit in a range.
+    History { range: String, json: bool },
+    /// Prints the details of how the version of a single revision was calculated.
+    Explain { rev: Option<String> },
+    /// Updates the versions of the members of the workspace in the current directory.
+    UpdateWorkspace,
+}
 
 fn main() {
     let config = MinverConfig::read_from_env().expect("Failed to parse configuration");
@@ -12,14 +26,199 @@ fn main() {
         println!("Failed to initialize log: {}", e);
     }
 
-    match get_version(&config) {
-        Ok(v) => println!("{}", v),
-        Err(e) => println!("Error: {}", e),
+    let result = parse_command(env::args().skip(1)).and_then(|command| run(command, &config));
+    if let Err(e) = result {
+        println!("Error: {}", e);
+        process::exit(1);
     }
 }
 
-fn get_version(config: &MinverConfig) -> Result<Version> {
+fn run(command: Command, config: &MinverConfig) -> Result<()> {
     let dir = env::current_dir()?;
-    let repo = Repository::open(dir.as_path())?;
-    minver_rs::get_version(&repo, &config)
+    if let Command::UpdateWorkspace = command {
+        build_utils::update_workspace_version(&dir.into_os_string(), config)?;
+        return Ok(());
+    }
+    if let Command::Version { rev: None } = command {
+        // Outside of a repository, e.g. in a source tarball, the version can come from elsewhere
+        let sourced = minver_rs::get_version_from_sources(dir.as_path(), config)?;
+        log::info!("Read version from {}", sourced.source);
+        println!("{}", sourced.version);
+        return Ok(());
+    }
+
+    let repo = minver_rs::open_repository(dir.as_path(), config)?;
+
+    match command {
+        Command::Version { rev } => {
+            let version = match rev {
+                Some(rev) => minver_rs::get_version_at(&repo, &rev, config)?,
+                None => minver_rs::get_version(&repo, config)?,
+            };
+            println!("{}", version);
+        }
+        Command::Explain { rev } => {
+            let calculation = match rev {
+                Some(rev) => minver_rs::get_version_detailed_at(&repo, &rev, config)?,
+                None => minver_rs::get_version_detailed(&repo, config)?,
+            };
+            print_explanation(&calculation);
+        }
+        Command::UpdateWorkspace => unreachable!("handled before opening the repository"),
+        Command::History { range, json } => {
+            let entries = minver_rs::get_version_history(&repo, &range, config)?;
+            if json {
+                entries.iter().for_each(|e| println!("{}", format_json(e)));
+            } else {
+                println!("{:<40}  {:<30}  {:<30}  HEIGHT", "COMMIT", "VERSION", "TAG");
+                entries.iter().for_each(|e| {
+                    println!(
+                        "{:<40}  {:<30}  {:<30}  {}",
+                        e.commit,
+                        e.version.to_string(),
+                        e.base_tag.as_deref().unwrap_or("-"),
+                        e.height
+                    )
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_command(args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("history") => {
+            args.next();
+            parse_history(args)
+        }
+        Some("explain") => {
+            args.next();
+            Ok(Command::Explain {
+                rev: parse_revision(args)?,
+            })
+        }
+        Some("update-workspace") => {
+            args.next();
+            match args.next() {
+                Some(arg) => Err(anyhow!("Unrecognized argument: {}", arg)),
+                None => Ok(Command::UpdateWorkspace),
+            }
+        }
+        _ => Ok(Command::Version {
+            rev: parse_revision(args)?,
+        }),
+    }
+}
+
+fn parse_revision(mut args: impl Iterator<Item = String>) -> Result<Option<String>> {
+    let mut rev = None;
+    while let Some(arg) = args.next() {
+        if arg == "--rev" {
+            rev = Some(args.next().ok_or(anyhow!("Missing value for --rev"))?);
+        } else if let Some(value) = arg.strip_prefix("--rev=") {
+            rev = Some(String::from(value));
+        } else {
+            return Err(anyhow!("Unrecognized argument: {}", arg));
+        }
+    }
+
+    Ok(rev.or_else(|| env::var(REVISION_VAR).ok()))
+}
+
+fn parse_history(args: impl Iterator<Item = String>) -> Result<Command> {
+    let mut range = None;
+    let mut json = false;
+    for arg in args {
+        if arg == "--json" {
+            json = true;
+        } else if range.is_none() && !arg.starts_with("--") {
+            range = Some(arg);
+        } else {
+            return Err(anyhow!("Unrecognized argument: {}", arg));
+        }
+    }
+
+    Ok(Command::History {
+        range: range.unwrap_or_else(|| String::from("HEAD")),
+        json,
+    })
+}
+
+fn print_explanation(calculation: &VersionCalculation) {
+    println!("Version:    {}", calculation.version);
+    match &calculation.base_tag {
+        Some(tag) => println!("Base tag:   {} ({})", tag.name, tag.commit),
+        None => println!("Base tag:   none"),
+    }
+    println!(
+        "Height:     {} ({})",
+        calculation.height, calculation.height_mode
+    );
+    match (
+        &calculation.incremented_level,
+        &calculation.increment_commit,
+    ) {
+        (Some(level), Some(commit)) => println!("Increment:  {} (required by {})", level, commit),
+        (Some(level), None) => println!("Increment:  {}", level),
+        (None, _) => println!("Increment:  none"),
+    }
+    println!(
+        "Dirty:      {}",
+        if calculation.is_dirty { "yes" } else { "no" }
+    );
+
+    println!("Candidates:");
+    if calculation.candidates.is_empty() {
+        println!("  none");
+    }
+    for (tag, height) in &calculation.candidates {
+        println!("  {} ({}) at height {}", tag.name, tag.commit, height);
+    }
+
+    if !calculation.discarded_tags.is_empty() {
+        println!("Discarded tags (lower precedence on the same commit):");
+        for tag in &calculation.discarded_tags {
+            println!("  {} ({})", tag.name, tag.commit);
+        }
+    }
+
+    println!("Config:");
+    if calculation.config_influences.is_empty() {
+        println!("  none");
+    }
+    for influence in &calculation.config_influences {
+        println!("  {}", influence);
+    }
+}
+
+fn format_json(entry: &HistoryEntry) -> String {
+    format!(
+        "{{\"commit\":\"{}\",\"version\":\"{}\",\"tag\":{},\"height\":{}}}",
+        entry.commit,
+        entry.version,
+        entry
+            .base_tag
+            .as_deref()
+            .map(json_string)
+            .unwrap_or_else(|| String::from("null")),
+        entry.height
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
@@ -1,96 +1,488 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
 
-use anyhow::Result;
-use git2::{Commit, Oid, Repository};
+use git2::{BranchType, Commit, ErrorCode, Oid, Repository, RepositoryOpenFlags};
 use radix_trie::Trie;
 
+use crate::ci;
+use crate::conventional;
+use crate::dirty;
+use crate::error::{MinverError, Result};
+use crate::height::{self, HeightMode};
+use crate::metadata::{self, MetadataContext};
+use crate::path_filter::PathFilter;
+use crate::shallow;
+
+/// The version tags of the repository, keyed by the id of the tagged commit. The tags of each
+/// commit are sorted from highest to lowest precedence.
+pub(crate) type TagIndex = Trie<String, Vec<VersionTag>>;
 pub use crate::semver::Version;
-use crate::MinverConfig;
+use crate::{BranchRule, MinverConfig, SemVerLevel, TagPattern};
+
+/// Opens the git repository containing the given path.
+///
+/// If [`MinverConfig::discover_repository`] is set, parent directories are searched for a
+/// repository the same way git does, respecting `GIT_DIR` and `GIT_CEILING_DIRECTORIES`.
+/// Otherwise, `path` must be the root of a repository.
+pub fn open_repository(path: &Path, config: &MinverConfig) -> Result<Repository> {
+    let result = if !config.discover_repository {
+        Repository::open(path)
+    } else if env::var_os("GIT_DIR").is_some() {
+        log::debug!("GIT_DIR is set, ignoring {:?}", path);
+        Repository::open_from_env()
+    } else {
+        Repository::open_ext(path, RepositoryOpenFlags::FROM_ENV, &[] as &[&OsStr])
+    };
 
-/// Calculates the version for the given repository and configuration.
+    let repository = result.map_err(|source| MinverError::RepositoryNotFound {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    log::info!("Using repository at {:?}", repository.path());
+    Ok(repository)
+}
+
+/// Calculates the version of `HEAD` for the given repository and configuration.
 pub fn get_version(repository: &Repository, config: &MinverConfig) -> Result<Version> {
+    let check_dirty = config.dirty_marker.is_some();
+    Ok(calculate_head_version(repository, check_dirty, config)?.version)
+}
+
+/// Calculates the version of the given revision for the given repository and configuration.
+///
+/// `rev` can be anything that resolves to a commit, such as a commit id, branch or tag name
+/// (see `git help revisions`).
+pub fn get_version_at(
+    repository: &Repository,
+    rev: &str,
+    config: &MinverConfig,
+) -> Result<Version> {
+    Ok(get_version_detailed_at(repository, rev, config)?.version)
+}
+
+/// Calculates the version of `HEAD` along with the details of how it was calculated.
+pub fn get_version_detailed(
+    repository: &Repository,
+    config: &MinverConfig,
+) -> Result<VersionCalculation> {
+    calculate_head_version(repository, true, config)
+}
+
+/// Calculates the version of `HEAD`. The working tree is only checked for uncommitted changes if
+/// `check_dirty` is set, since that is slow in large repositories and only needed for
+/// [`MinverConfig::dirty_marker`] and [`VersionCalculation::is_dirty`].
+pub(crate) fn calculate_head_version(
+    repository: &Repository,
+    check_dirty: bool,
+    config: &MinverConfig,
+) -> Result<VersionCalculation> {
     log::info!("Getting version for {:?}", repository.path());
-    log::debug!("Loaded config: {:?}", repository.path());
-    let tags = get_tags(repository, &config.tag_prefix)?;
+    let head = get_head(repository)?;
+    let branch = repository
+        .head()
+        .ok()
+        .filter(|reference| reference.is_branch())
+        .and_then(|reference| reference.shorthand().map(String::from));
+    let mut calculation = calculate_version(repository, &head, branch.as_deref(), config)?;
 
-    let (version, height) = find_latest_versions(&tags, &repository)?
-        .into_iter()
-        .max_by(|(v1, _h1), (v2, _h2)| v1.cmp_precedence(v2))
-        .unwrap_or_else(|| {
+    calculation.is_dirty = check_dirty && dirty::is_dirty(repository, config)?;
+    if let (true, Some(marker)) = (calculation.is_dirty, &config.dirty_marker) {
+        log::debug!("Working tree is dirty, adding {}", marker);
+        calculation.version = marker.apply(calculation.version, config);
+        calculation
+            .config_influences
+            .push(ConfigInfluence::new("dirty_marker", marker));
+    }
+
+    Ok(calculation)
+}
+
+/// Calculates the version of the given revision along with the details of how it was calculated.
+///
+/// Since the revision is not necessarily checked out, the working tree is not checked for
+/// uncommitted changes.
+pub fn get_version_detailed_at(
+    repository: &Repository,
+    rev: &str,
+    config: &MinverConfig,
+) -> Result<VersionCalculation> {
+    log::info!("Getting version of {} for {:?}", rev, repository.path());
+    let commit = resolve_revision(repository, rev)?;
+    let branch = repository
+        .find_branch(rev, BranchType::Local)
+        .ok()
+        .map(|_| rev);
+    calculate_version(repository, &commit, branch, config)
+}
+
+/// A tag whose name (after removing the tag prefix) is a valid version.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VersionTag {
+    /// The full name of the tag.
+    pub name: String,
+    /// The version parsed from the tag name.
+    pub version: Version,
+    /// The id of the tagged commit.
+    pub commit: Oid,
+}
+
+/// A configuration value that influenced a calculated version.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigInfluence {
+    /// The name of the configuration field, e.g. `auto_increment_level`.
+    pub option: &'static str,
+    /// The configured value.
+    pub value: String,
+}
+
+/// The result of a version calculation, including the details of how the version was chosen.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VersionCalculation {
+    /// The calculated version.
+    pub version: Version,
+    /// The tag that the version is based on, or `None` if no tag was found.
+    pub base_tag: Option<VersionTag>,
+    /// The number of commits between the calculated commit and the base tag.
+    pub height: u32,
+    /// How the height was measured.
+    pub height_mode: HeightMode,
+    /// Every tag found in the history of the commit, along with its height.
+    pub candidates: Vec<(VersionTag, u32)>,
+    /// The level that was incremented, or `None` if the version was not incremented.
+    pub incremented_level: Option<SemVerLevel>,
+    /// Tags that were ignored because a tag with a higher precedence is on the same commit.
+    pub discarded_tags: Vec<VersionTag>,
+    /// The configuration values that influenced the version.
+    pub config_influences: Vec<ConfigInfluence>,
+    /// Whether the index or working tree has uncommitted changes. Only checked for `HEAD`, by
+    /// [`get_version_detailed`] or when [`MinverConfig::dirty_marker`] is set.
+    pub is_dirty: bool,
+    /// The commit whose message required the incremented level, e.g. with a `Version-Bump`
+    /// trailer or a Conventional Commits type, or `None` if the level comes from the configuration.
+    pub increment_commit: Option<Oid>,
+}
+
+fn calculate_version(
+    repository: &Repository,
+    commit: &Commit,
+    branch: Option<&str>,
+    config: &MinverConfig,
+) -> Result<VersionCalculation> {
+    log::debug!("Loaded config: {:?}", config);
+    let (branch_config, branch_rule) = apply_branch_rules(branch, config)?;
+    let config = &branch_config;
+
+    let mut tags = get_tags(repository, &config.tag_patterns())?;
+    ci::add_ci_tag(repository, commit, &mut tags, config)?;
+    let boundary = shallow::add_boundary_tags(repository, &mut tags, config)?;
+    let path_filter = PathFilter::new(repository, &config.path_filter);
+    let candidates = find_latest_versions(&tags, commit, &path_filter, config)?;
+    let candidates = shallow::apply_shallow_policy(candidates, &boundary, config)?;
+    let candidates = apply_height_mode(repository, commit, candidates, &path_filter, config)?;
+    let resolution = conventional::resolve_increment_level(
+        repository,
+        commit,
+        &candidates,
+        &path_filter,
+        config,
+    )?;
+    let discarded_tags = candidates
+        .iter()
+        .flat_map(|(tag, _height)| get_commit_tags(&tags, tag.commit).iter().skip(1))
+        .cloned()
+        .collect();
+
+    let context = MetadataContext::new(repository, commit, branch)?;
+    let mut calculation = calculate_from_candidates(
+        candidates,
+        discarded_tags,
+        is_tagged(&tags, commit.id()),
+        &context,
+        &resolution.level,
+        config,
+    )?;
+    if let Some(rule) = branch_rule {
+        calculation
+            .config_influences
+            .push(ConfigInfluence::new("branch_rules", &rule));
+    }
+    if calculation.incremented_level.is_some() {
+        calculation.increment_commit = resolution.commit;
+        if let Some(trailer) = &resolution.trailer {
+            calculation
+                .config_influences
+                .push(ConfigInfluence::new("bump_trailers", trailer));
+        }
+    }
+    Ok(calculation)
+}
+
+/// Replaces `prerelease_identifier` with the identifier of the first branch rule that matches
+/// `branch`, which is `None` for a detached `HEAD`. Returns the matching rule as well.
+pub(crate) fn apply_branch_rules(
+    branch: Option<&str>,
+    config: &MinverConfig,
+) -> Result<(MinverConfig, Option<BranchRule>)> {
+    let mut branch_config = config.clone();
+    for rule in &config.branch_rules {
+        if let Some(identifier) = rule.prerelease_identifier(branch)? {
+            log::debug!(
+                "Branch {} matches rule {}, using prerelease identifier {}",
+                branch.unwrap_or("HEAD"),
+                rule,
+                identifier
+            );
+            branch_config.prerelease_identifier = identifier;
+            return Ok((branch_config, Some(rule.clone())));
+        }
+    }
+
+    Ok((branch_config, None))
+}
+
+/// Calculates the version from the candidate tags found in the history of a commit.
+/// `is_tagged` is whether the commit itself has a version tag, see [`is_tagged`], and
+/// `increment_level` is the level chosen for [`MinverConfig::auto_increment_level`].
+pub(crate) fn calculate_from_candidates(
+    candidates: Vec<(VersionTag, u32)>,
+    discarded_tags: Vec<VersionTag>,
+    is_tagged: bool,
+    context: &MetadataContext,
+    increment_level: &SemVerLevel,
+    config: &MinverConfig,
+) -> Result<VersionCalculation> {
+    let mut influences = vec![];
+    if !config.tag_pattern.is_empty() {
+        let patterns: Vec<String> = config.tag_pattern.iter().map(|p| p.to_string()).collect();
+        influences.push(ConfigInfluence::new("tag_pattern", &patterns.join(" ")));
+    } else if !config.tag_prefix.is_empty() {
+        influences.push(ConfigInfluence::new("tag_prefix", &config.tag_prefix));
+    }
+    if config.first_parent {
+        influences.push(ConfigInfluence::new("first_parent", &config.first_parent));
+    }
+    if !config.path_filter.is_empty() {
+        influences.push(ConfigInfluence::new(
+            "path_filter",
+            &config.path_filter.join(" "),
+        ));
+    }
+    if config.height_mode != HeightMode::ShortestPath {
+        influences.push(ConfigInfluence::new("height_mode", &config.height_mode));
+    }
+
+    let latest_candidate = latest_candidate(&candidates).cloned();
+
+    let (base_tag, version, height) = match latest_candidate {
+        Some((tag, height)) => {
+            let version = tag.version.clone();
+            (Some(tag), version, height)
+        }
+        None => {
             let v = Version::default(&config.prerelease_identifier);
             log::debug!("No tags found, using {}", v);
-            (v, 0)
-        });
+            influences.push(ConfigInfluence::new(
+                "prerelease_identifier",
+                &config.prerelease_identifier,
+            ));
+            (None, v, 0)
+        }
+    };
+
+    let effective_height = if config.ignore_height && height != 0 {
+        log::debug!("Ignoring height of {} since the latest tag", height);
+        influences.push(ConfigInfluence::new("ignore_height", &config.ignore_height));
+        0
+    } else {
+        height
+    };
 
-    let version = if height == 0 {
+    let (version, incremented_level) = if effective_height == 0 {
         log::debug!("Height is zero, leaving tag as-is: {}", version);
-        version
+        (version, None)
     } else {
         log::debug!(
             "Height is non-zero, removing metadata and incrementing {} version from {}",
-            &config.auto_increment_level,
+            increment_level,
             version
         );
-        version
-            .with_height(height, &config.prerelease_identifier)
+        if version.prerelease.is_none() {
+            influences.push(ConfigInfluence::new(
+                "prerelease_identifier",
+                &config.prerelease_identifier,
+            ));
+        }
+        influences.push(ConfigInfluence::new(
+            "auto_increment_level",
+            &config.auto_increment_level,
+        ));
+        let version = version
+            .with_height(effective_height, &config.prerelease_identifier)
             .without_metadata()
-            .with_incremented_level(&config.auto_increment_level)
+            .with_incremented_level(increment_level);
+        (version, Some(increment_level.clone()))
     };
 
-    match &config.build_metadata {
-        Some(metadata) => {
+    let version = match &config.minimum_major_minor {
+        Some(minimum) if !is_tagged && version.is_below(minimum) => {
+            let minimum_version = Version {
+                major: minimum.major,
+                minor: minimum.minor,
+                ..Version::default(&config.prerelease_identifier)
+            };
+            let minimum_version = if effective_height == 0 {
+                minimum_version
+            } else {
+                minimum_version.with_height(effective_height, &config.prerelease_identifier)
+            };
+            log::debug!(
+                "{} is below the minimum major.minor {}, using {}",
+                version,
+                minimum,
+                minimum_version
+            );
+            influences.push(ConfigInfluence::new("minimum_major_minor", minimum));
+            minimum_version
+        }
+        _ => version,
+    };
+
+    let version = match &config.build_metadata {
+        Some(template) => {
+            let metadata = metadata::expand(template, context, height, base_tag.as_ref())?;
             log::debug!("Appending configured metadata: {}", metadata);
-            Ok(version.with_appended_metadata(&metadata))
+            influences.push(ConfigInfluence::new("build_metadata", template));
+            version.with_appended_metadata(&metadata)
+        }
+        None => version,
+    };
+
+    Ok(VersionCalculation {
+        version,
+        base_tag,
+        height,
+        height_mode: config.height_mode,
+        candidates,
+        discarded_tags,
+        incremented_level,
+        config_influences: influences,
+        is_dirty: false,
+        increment_commit: None,
+    })
+}
+
+impl ConfigInfluence {
+    fn new(option: &'static str, value: &dyn fmt::Display) -> ConfigInfluence {
+        ConfigInfluence {
+            option,
+            value: value.to_string(),
         }
-        None => Ok(version),
     }
 }
 
-fn find_latest_versions(
-    tags: &Trie<String, Version>,
-    repository: &Repository,
-) -> Result<Vec<(Version, u32)>> {
-    let mut current_height: u32 = 0;
-    let mut results: Vec<(Version, u32)> = vec![];
+impl fmt::Display for ConfigInfluence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.option, self.value)
+    }
+}
+
+pub(crate) fn find_latest_versions(
+    tags: &TagIndex,
+    commit: &Commit,
+    path_filter: &PathFilter,
+    config: &MinverConfig,
+) -> Result<Vec<(VersionTag, u32)>> {
+    let mut results: Vec<(VersionTag, u32)> = vec![];
 
+    // Commits that do not count towards the height are pushed to the front of the queue so that
+    // commits are always checked in order of increasing height
     let mut checked_commits: HashSet<Oid> = HashSet::new();
-    let mut commits_to_check = vec![repository.head()?.peel_to_commit()?];
-
-    while !commits_to_check.is_empty() {
-        let mut parent_commits: Vec<Vec<Commit>> = vec![];
-        for commit in commits_to_check {
-            log::trace!("Checking {:?}", &commit);
-            if checked_commits.contains(&commit.id()) {
-                log::trace!("Commit has already been checked, skipping: {:?}", &commit);
-                continue;
+    let mut commits_to_check = VecDeque::from(vec![(commit.clone(), 0)]);
+
+    while let Some((commit, height)) = commits_to_check.pop_front() {
+        log::trace!("Checking {:?}", &commit);
+        if checked_commits.contains(&commit.id()) {
+            log::trace!("Commit has already been checked, skipping: {:?}", &commit);
+            continue;
+        }
+        checked_commits.insert(commit.id());
+
+        // This could be optimized further by using Trie::remove rather than Trie::get to avoid
+        // calling VersionTag::clone
+        match get_commit_tags(tags, commit.id()).first() {
+            Some(tag) => {
+                log::trace!("Found candidate version: {} at {:?}", &tag.version, &commit);
+                results.push((tag.clone(), height))
             }
-            checked_commits.insert(commit.id());
-
-            // This could be optimized further by using Trie::remove rather than Trie::get to avoid
-            // calling Version::clone
-            match tags.get(&commit.id().to_string()) {
-                Some(v) => {
-                    log::trace!("Found candidate version: {} at {:?}", &v, &commit);
-                    results.push((v.clone(), current_height))
+            None => {
+                if path_filter.weight(&commit)? == 0 {
+                    get_parents(&commit, config)
+                        .into_iter()
+                        .for_each(|parent| commits_to_check.push_front((parent, height)));
+                } else {
+                    get_parents(&commit, config)
+                        .into_iter()
+                        .for_each(|parent| commits_to_check.push_back((parent, height + 1)));
                 }
-                None => parent_commits.push(commit.parents().collect()),
             }
         }
-
-        commits_to_check = parent_commits.into_iter().flatten().collect();
-        current_height = current_height + 1;
     }
 
     Ok(results)
 }
 
-fn get_tags(repository: &Repository, tag_prefix: &str) -> Result<Trie<String, Version>> {
+/// Gets the candidate with the highest precedence, which the version is based on.
+pub(crate) fn latest_candidate(candidates: &[(VersionTag, u32)]) -> Option<&(VersionTag, u32)> {
+    candidates
+        .iter()
+        .max_by(|(t1, _h1), (t2, _h2)| t1.version.cmp_precedence(&t2.version))
+}
+
+/// Replaces the heights of `candidates`, which are found using the shortest path, with heights
+/// measured using [`MinverConfig::height_mode`].
+pub(crate) fn apply_height_mode(
+    repository: &Repository,
+    commit: &Commit,
+    candidates: Vec<(VersionTag, u32)>,
+    path_filter: &PathFilter,
+    config: &MinverConfig,
+) -> Result<Vec<(VersionTag, u32)>> {
+    candidates
+        .into_iter()
+        .map(|(tag, shortest_height)| {
+            let height = height::measure_height(
+                repository,
+                commit,
+                tag.commit,
+                path_filter,
+                config.height_mode,
+            )?
+            .unwrap_or(shortest_height);
+            Ok((tag, height))
+        })
+        .collect()
+}
+
+/// Gets the parents of a commit that are followed when searching for tags.
+pub(crate) fn get_parents<'a>(commit: &Commit<'a>, config: &MinverConfig) -> Vec<Commit<'a>> {
+    if config.first_parent {
+        commit.parents().take(1).collect()
+    } else {
+        commit.parents().collect()
+    }
+}
+
+pub(crate) fn get_tags(repository: &Repository, tag_patterns: &[TagPattern]) -> Result<TagIndex> {
     // Note: A trie may or may not actually be more performant than a map, but I'm using it anyways
     // because it's theoretically more efficient and I don't get to use tries very often :)
-    let mut trie = Trie::new();
+    let mut trie: TagIndex = Trie::new();
 
     let tags = repository.tag_names(None)?;
-    tags.iter()
+    let mut version_tags: Vec<VersionTag> = tags
+        .iter()
         .filter_map(|opt| {
             if opt.is_none() {
                 log::debug!("Found non UTF-8 tag, ignoring it");
@@ -98,20 +490,24 @@ fn get_tags(repository: &Repository, tag_prefix: &str) -> Result<Trie<String, Ve
             opt
         })
         .filter_map(|tag_name| {
-            let result_opt = tag_name.strip_prefix(tag_prefix).map(|version| {
-                Ok((
-                    Version::parse(version)?,
-                    get_tagged_commit(&repository, tag_name)?,
-                ))
-            });
+            let result_opt = tag_patterns
+                .iter()
+                .find_map(|pattern| pattern.extract_version(tag_name))
+                .map(|version| {
+                    Ok(VersionTag {
+                        name: String::from(tag_name),
+                        version: Version::parse(version)?,
+                        commit: get_tagged_commit(repository, tag_name)?.id(),
+                    })
+                });
 
             if result_opt.is_none() {
-                log::trace!("Ignoring tag that does not match prefix: {}", tag_name);
+                log::trace!("Ignoring tag that does not match pattern: {}", tag_name);
             }
 
             result_opt
         })
-        .filter_map(|result: Result<(Version, Commit)>| {
+        .filter_map(|result: Result<VersionTag>| {
             if result.is_err() {
                 log::warn!(
                     "Error occurred while handling tag: {}",
@@ -120,14 +516,71 @@ fn get_tags(repository: &Repository, tag_prefix: &str) -> Result<Trie<String, Ve
             }
             result.ok()
         })
-        .for_each(|(version, commit)| {
-            log::trace!("Found tag {} for {:?}", version, &commit);
-            trie.insert(commit.id().to_string(), version);
-        });
+        .collect();
+
+    // Sort by name as well as precedence so that the chosen tag does not depend on the order of
+    // tag_names when tags on the same commit have equal precedence (e.g. only metadata differs)
+    version_tags.sort_by(|t1, t2| {
+        t2.version
+            .cmp_precedence(&t1.version)
+            .then_with(|| t1.name.cmp(&t2.name))
+    });
+
+    for tag in version_tags {
+        let key = tag.commit.to_string();
+        match trie.get_mut(&key) {
+            Some(commit_tags) => {
+                log::debug!(
+                    "Ignoring tag {} since {} has a higher precedence on the same commit",
+                    tag.name,
+                    commit_tags[0].name
+                );
+                commit_tags.push(tag);
+            }
+            None => {
+                log::trace!("Found tag {} for {:?}", tag.name, tag.commit);
+                trie.insert(key, vec![tag]);
+            }
+        }
+    }
 
     Ok(trie)
 }
 
+/// Returns `true` if `commit` has a version tag. Unlike a candidate at height 0, this excludes
+/// the placeholder tags where the history of a shallow clone ends and the tags of ancestors that
+/// do not match [`MinverConfig::path_filter`].
+pub(crate) fn is_tagged(tags: &TagIndex, commit: Oid) -> bool {
+    get_commit_tags(tags, commit)
+        .iter()
+        .any(|tag| !shallow::is_boundary_tag(tag))
+}
+
+/// Gets the version tags of a commit, sorted from highest to lowest precedence.
+pub(crate) fn get_commit_tags(tags: &TagIndex, commit: Oid) -> &[VersionTag] {
+    tags.get(&commit.to_string())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+pub(crate) fn get_head(repository: &Repository) -> Result<Commit<'_>> {
+    let head = repository.head().map_err(|e| match e.code() {
+        ErrorCode::UnbornBranch => MinverError::UnbornHead,
+        _ => MinverError::Git(e),
+    })?;
+    Ok(head.peel_to_commit()?)
+}
+
+pub(crate) fn resolve_revision<'a>(repository: &'a Repository, rev: &str) -> Result<Commit<'a>> {
+    repository
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|source| MinverError::RevisionNotFound {
+            rev: String::from(rev),
+            source,
+        })
+}
+
 fn get_tagged_commit<'a>(repository: &'a Repository, tag_name: &'a str) -> Result<Commit<'a>> {
     log::trace!("Getting commit for {}", tag_name);
     let object = repository.revparse_single(&format!("refs/tags/{}", tag_name))?;
@@ -0,0 +1,57 @@
+use git2::{Commit, DiffOptions, Repository};
+
+use crate::error::Result;
+
+/// Determines which commits count towards the height, based on the paths that they change.
+pub(crate) struct PathFilter<'a> {
+    repository: &'a Repository,
+    paths: &'a [String],
+}
+
+impl<'a> PathFilter<'a> {
+    /// Creates a filter for the given paths. If `paths` is empty, every commit is counted.
+    pub fn new(repository: &'a Repository, paths: &'a [String]) -> PathFilter<'a> {
+        PathFilter { repository, paths }
+    }
+
+    /// Returns the amount that `commit` adds to the height: `1` if it changes any of the filtered
+    /// paths, otherwise `0`.
+    pub fn weight(&self, commit: &Commit) -> Result<u32> {
+        if self.paths.is_empty() || self.touches_paths(commit)? {
+            Ok(1)
+        } else {
+            log::trace!("Commit does not change filtered paths: {:?}", commit);
+            Ok(0)
+        }
+    }
+
+    fn touches_paths(&self, commit: &Commit) -> Result<bool> {
+        let tree = commit.tree()?;
+        let mut options = DiffOptions::new();
+        self.paths.iter().for_each(|path| {
+            options.pathspec(path);
+        });
+
+        if commit.parent_count() == 0 {
+            let diff = self
+                .repository
+                .diff_tree_to_tree(None, Some(&tree), Some(&mut options))?;
+            return Ok(diff.deltas().len() > 0);
+        }
+
+        // Like git log, a merge commit is only counted if it differs from every parent, since
+        // otherwise the changes were already counted on the merged branch
+        for parent in commit.parents() {
+            let diff = self.repository.diff_tree_to_tree(
+                Some(&parent.tree()?),
+                Some(&tree),
+                Some(&mut options),
+            )?;
+            if diff.deltas().len() == 0 {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
@@ -1,9 +1,11 @@
-use anyhow::{anyhow, Result};
 use regex::Regex;
 use strum_macros::{Display, EnumString};
 
 use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{MinverError, Result};
 
 /// Represents a semver 2.0 version.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -17,13 +19,20 @@ pub struct Version {
     pub build_metadata: Option<String>,
 }
 
-#[derive(EnumString, Display, Debug)]
+#[derive(EnumString, Display, Debug, PartialEq, Eq, Clone)]
 pub enum Level {
     Major,
     Minor,
     Patch,
 }
 
+/// A `major.minor` pair, used as a lower bound for calculated versions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MajorMinor {
+    pub major: u32,
+    pub minor: u32,
+}
+
 impl Version {
     pub fn parse(version: &str) -> Result<Version> {
         log::trace!("Parsing version: {}", version);
@@ -31,15 +40,13 @@ impl Version {
         let pattern = "^(?P<major>0|[1-9]\\d*)\\.(?P<minor>0|[1-9]\\d*)\\.(?P<patch>0|[1-9]\\d*)(?:-(?P<prerelease>(?:0|[1-9]\\d*|\\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\\.(?:0|[1-9]\\d*|\\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?(?:\\+(?P<buildmetadata>[0-9a-zA-Z-]+(?:\\.[0-9a-zA-Z-]+)*))?$";
 
         let re = Regex::new(pattern).unwrap();
-        let captures = re.captures(version).ok_or(anyhow!(
-            "Version is not a valid semver 2.0 version: {}",
-            version
-        ))?;
+        let invalid = || MinverError::InvalidVersion(String::from(version));
+        let captures = re.captures(version).ok_or_else(invalid)?;
 
         Ok(Version {
-            major: captures.name("major").unwrap().as_str().parse().unwrap(),
-            minor: captures.name("minor").unwrap().as_str().parse().unwrap(),
-            patch: captures.name("patch").unwrap().as_str().parse().unwrap(),
+            major: captures["major"].parse().map_err(|_| invalid())?,
+            minor: captures["minor"].parse().map_err(|_| invalid())?,
+            patch: captures["patch"].parse().map_err(|_| invalid())?,
             prerelease: captures
                 .name("prerelease")
                 .map(|m| String::from(m.as_str())),
@@ -120,6 +127,11 @@ impl Version {
         }
     }
 
+    /// Returns `true` if the major and minor components of this version are lower than `minimum`.
+    pub fn is_below(&self, minimum: &MajorMinor) -> bool {
+        (self.major, self.minor) < (minimum.major, minimum.minor)
+    }
+
     pub fn with_height(self, height: u32, default_prerelease: &str) -> Version {
         Version {
             prerelease: Some(format!(
@@ -170,6 +182,37 @@ impl Version {
     }
 }
 
+impl FromStr for Version {
+    type Err = MinverError;
+
+    fn from_str(version: &str) -> Result<Version> {
+        Version::parse(version)
+    }
+}
+
+impl FromStr for MajorMinor {
+    type Err = MinverError;
+
+    fn from_str(major_minor: &str) -> Result<MajorMinor> {
+        let pattern = "^(?P<major>0|[1-9]\\d*)\\.(?P<minor>0|[1-9]\\d*)$";
+
+        let re = Regex::new(pattern).unwrap();
+        let invalid = || MinverError::InvalidMajorMinor(String::from(major_minor));
+        let captures = re.captures(major_minor).ok_or_else(invalid)?;
+
+        Ok(MajorMinor {
+            major: captures["major"].parse().map_err(|_| invalid())?,
+            minor: captures["minor"].parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for MajorMinor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
 impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -235,7 +278,33 @@ mod test {
             Version::parse("1.2.3-alpha.1.1+a1b2c3").unwrap()
         );
 
-        assert!(Version::parse("v1.2.3").is_err())
+        assert!(matches!(
+            Version::parse("v1.2.3"),
+            Err(MinverError::InvalidVersion(_))
+        ));
+        assert!(matches!(
+            Version::parse("99999999999.0.0"),
+            Err(MinverError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_major_minor() {
+        assert_eq!(
+            MajorMinor { major: 2, minor: 0 },
+            "2.0".parse::<MajorMinor>().unwrap()
+        );
+        assert_eq!(
+            MajorMinor {
+                major: 10,
+                minor: 11
+            },
+            "10.11".parse::<MajorMinor>().unwrap()
+        );
+
+        assert!("2".parse::<MajorMinor>().is_err());
+        assert!("2.0.0".parse::<MajorMinor>().is_err());
+        assert!("v2.0".parse::<MajorMinor>().is_err());
     }
 
     #[test]
@@ -257,7 +326,7 @@ mod test {
             create_version(2, 2, 3, None, None),
         ];
 
-        versions.sort_by(|v1, v2| v1.cmp_precedence(&v2));
+        versions.sort_by(|v1, v2| v1.cmp_precedence(v2));
 
         assert_eq!(versions, expected_versions);
     }
@@ -297,7 +366,7 @@ mod test {
             create_version(1, 0, 0, None, None),
         ];
 
-        versions.sort_by(|v1, v2| v1.cmp_precedence(&v2));
+        versions.sort_by(|v1, v2| v1.cmp_precedence(v2));
 
         assert_eq!(versions, expected_versions);
     }
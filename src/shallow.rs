@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use git2::{ErrorCode, Oid, Repository, Sort};
+use strum_macros::{Display, EnumString};
+
+use crate::error::{MinverError, Result};
+use crate::minver_core::{TagIndex, VersionTag};
+use crate::{MinverConfig, Version};
+
+/// The name given to the placeholder tags on the commits where the history of a shallow clone
+/// ends. Since ref names cannot contain spaces, this never clashes with a real tag.
+const BOUNDARY_TAG_NAME: &str = "(shallow clone boundary)";
+
+/// Determines what happens when the search for version tags reaches the end of the history of a
+/// shallow clone, e.g. one created by `git clone --depth 1`.
+#[derive(EnumString, Display, Debug, PartialEq, Eq, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum ShallowPolicy {
+    /// Fail with [`MinverError::ShallowRepository`].
+    Error,
+    /// Log a warning and calculate the version from the tags that were found.
+    Warn,
+    /// Treat the end of the history like a tag with the default version, so the height is
+    /// measured up to the oldest fetched commit.
+    Limit,
+}
+
+/// Adds a placeholder tag with the default version to every untagged commit where the history of
+/// the repository ends, so that searches stop there and report the height of the boundary.
+/// Returns the ids of those commits, which is empty unless the repository is a shallow clone.
+pub(crate) fn add_boundary_tags(
+    repository: &Repository,
+    tags: &mut TagIndex,
+    config: &MinverConfig,
+) -> Result<HashSet<Oid>> {
+    let mut boundary = HashSet::new();
+    if !repository.is_shallow() {
+        return Ok(boundary);
+    }
+
+    for commit in read_shallow_commits(repository)? {
+        let key = commit.to_string();
+        if tags.get(&key).is_none() {
+            log::debug!("History of shallow clone ends at {}", commit);
+            tags.insert(
+                key,
+                vec![VersionTag {
+                    name: String::from(BOUNDARY_TAG_NAME),
+                    version: Version::default(&config.prerelease_identifier),
+                    commit,
+                }],
+            );
+            boundary.insert(commit);
+        }
+    }
+
+    Ok(boundary)
+}
+
+/// Returns `true` if `tag` is a placeholder added by [`add_boundary_tags`].
+pub(crate) fn is_boundary_tag(tag: &VersionTag) -> bool {
+    tag.name == BOUNDARY_TAG_NAME
+}
+
+/// Applies [`MinverConfig::shallow_policy`] to candidates that were found by searching a
+/// repository with the placeholder tags added by [`add_boundary_tags`].
+pub(crate) fn apply_shallow_policy(
+    candidates: Vec<(VersionTag, u32)>,
+    boundary: &HashSet<Oid>,
+    config: &MinverConfig,
+) -> Result<Vec<(VersionTag, u32)>> {
+    let boundary_commit = match candidates
+        .iter()
+        .find(|(tag, _)| boundary.contains(&tag.commit))
+    {
+        Some((tag, _)) => tag.commit,
+        None => return Ok(candidates),
+    };
+
+    match config.shallow_policy {
+        ShallowPolicy::Error => Err(MinverError::ShallowRepository {
+            commit: boundary_commit,
+        }),
+        ShallowPolicy::Warn => {
+            log::warn!(
+                "The history of this shallow clone ends at {}, the version may be incorrect",
+                boundary_commit
+            );
+            Ok(candidates
+                .into_iter()
+                .filter(|(tag, _)| !boundary.contains(&tag.commit))
+                .collect())
+        }
+        ShallowPolicy::Limit => {
+            log::info!(
+                "The history of this shallow clone ends at {}, measuring the height from there",
+                boundary_commit
+            );
+            Ok(candidates)
+        }
+    }
+}
+
+/// Lists the commits that are reachable from `commit` but not from `hidden`, like
+/// `git rev-list --topo-order --reverse hidden..commit`, so parents come before their children.
+///
+/// libgit2 fails on the missing parents of the commits where the history of a shallow clone ends,
+/// so shallow clones are walked here instead, treating those commits like root commits.
+pub(crate) fn walk(
+    repository: &Repository,
+    commit: Oid,
+    hidden: Option<Oid>,
+    first_parent: bool,
+) -> Result<Vec<Oid>> {
+    if !repository.is_shallow() {
+        let mut revwalk = repository.revwalk()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        if first_parent {
+            revwalk.simplify_first_parent()?;
+        }
+        revwalk.push(commit)?;
+        if let Some(hidden) = hidden {
+            revwalk.hide(hidden)?;
+        }
+        return Ok(revwalk.collect::<std::result::Result<_, _>>()?);
+    }
+
+    let boundary = read_shallow_commits(repository)?;
+    let parents = |oid: Oid| -> Result<Vec<Oid>> {
+        if boundary.contains(&oid) {
+            return Ok(vec![]);
+        }
+        let commit = repository
+            .find_commit(oid)
+            .map_err(|error| match error.code() {
+                ErrorCode::NotFound => MinverError::ShallowRepository { commit: oid },
+                _ => error.into(),
+            })?;
+        let parent_ids = commit.parent_ids();
+        Ok(if first_parent {
+            parent_ids.take(1).collect()
+        } else {
+            parent_ids.collect()
+        })
+    };
+
+    let mut excluded = HashSet::new();
+    let mut commits_to_check: Vec<Oid> = hidden.into_iter().collect();
+    while let Some(oid) = commits_to_check.pop() {
+        if excluded.insert(oid) {
+            commits_to_check.extend(parents(oid)?);
+        }
+    }
+
+    // A commit is only added once all of its parents have been added, which is the case when it
+    // is popped for the second time
+    let mut commits = vec![];
+    let mut visited = HashSet::new();
+    let mut commits_to_check = vec![(commit, false)];
+    while let Some((oid, parents_added)) = commits_to_check.pop() {
+        if parents_added {
+            commits.push(oid);
+        } else if !excluded.contains(&oid) && visited.insert(oid) {
+            commits_to_check.push((oid, true));
+            for parent in parents(oid)?.into_iter().rev() {
+                commits_to_check.push((parent, false));
+            }
+        }
+    }
+    Ok(commits)
+}
+
+/// Reads the commits where the history of a shallow clone ends.
+fn read_shallow_commits(repository: &Repository) -> Result<HashSet<Oid>> {
+    let path = shallow_file_path(repository);
+    let contents = fs::read_to_string(&path).map_err(|source| MinverError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(Oid::from_str(line.trim())?))
+        .collect()
+}
+
+fn shallow_file_path(repository: &Repository) -> PathBuf {
+    // Linked worktrees share the shallow file of the main repository
+    let commondir = repository.path().join("commondir");
+    match fs::read_to_string(&commondir) {
+        Ok(relative) => repository.path().join(relative.trim()).join("shallow"),
+        Err(_) => repository.path().join("shallow"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_shallow_policy() {
+        assert_eq!(Ok(ShallowPolicy::Error), "error".parse());
+        assert_eq!(Ok(ShallowPolicy::Warn), "warn".parse());
+        assert_eq!(Ok(ShallowPolicy::Limit), "limit".parse());
+        assert!("ignore".parse::<ShallowPolicy>().is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use regex::Regex;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{MinverError, Result};
+
+const VERSION_PLACEHOLDER: &str = "{version}";
+const REGEX_PREFIX: &str = "regex:";
+const VERSION_GROUP: &str = "version";
+
+/// Describes where the version is located in a tag name.
+///
+/// A pattern is either a template containing a single `{version}` placeholder, such as
+/// `mycrate@{version}` or `v{version}-linux`, or a regular expression prefixed with `regex:`
+/// containing a capture group named `version`, such as `regex:^release/v(?P<version>.+)-final$`.
+#[derive(Debug, Clone)]
+pub enum TagPattern {
+    /// Text that surrounds the version in the tag name.
+    Template { prefix: String, suffix: String },
+    /// A regular expression with a `version` capture group.
+    Regex(Regex),
+}
+
+impl TagPattern {
+    /// Creates a pattern that matches tags consisting of `prefix` followed by the version.
+    pub fn with_prefix(prefix: &str) -> TagPattern {
+        TagPattern::Template {
+            prefix: String::from(prefix),
+            suffix: String::new(),
+        }
+    }
+
+    /// Extracts the version part of `tag_name`, or returns `None` if the tag does not match.
+    pub fn extract_version<'a>(&self, tag_name: &'a str) -> Option<&'a str> {
+        match self {
+            TagPattern::Template { prefix, suffix } => tag_name
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_suffix(suffix.as_str())),
+            TagPattern::Regex(re) => re
+                .captures(tag_name)
+                .and_then(|captures| captures.name(VERSION_GROUP))
+                .map(|m| m.as_str()),
+        }
+    }
+}
+
+impl FromStr for TagPattern {
+    type Err = MinverError;
+
+    fn from_str(pattern: &str) -> Result<TagPattern> {
+        let invalid =
+            |reason: &str| MinverError::InvalidTagPattern(format!("{} ({})", pattern, reason));
+
+        match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(expression) => {
+                let re = Regex::new(expression).map_err(|e| invalid(&e.to_string()))?;
+                if re
+                    .capture_names()
+                    .flatten()
+                    .any(|name| name == VERSION_GROUP)
+                {
+                    Ok(TagPattern::Regex(re))
+                } else {
+                    Err(invalid("missing a capture group named version"))
+                }
+            }
+            None => {
+                let mut parts = pattern.split(VERSION_PLACEHOLDER);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(prefix), Some(suffix), None) => Ok(TagPattern::Template {
+                        prefix: String::from(prefix),
+                        suffix: String::from(suffix),
+                    }),
+                    _ => Err(invalid("must contain {version} exactly once")),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TagPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagPattern::Template { prefix, suffix } => {
+                write!(f, "{}{}{}", prefix, VERSION_PLACEHOLDER, suffix)
+            }
+            TagPattern::Regex(re) => write!(f, "{}{}", REGEX_PREFIX, re.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_template_with_prefix_and_suffix() {
+        let pattern: TagPattern = "release/v{version}-final".parse().unwrap();
+
+        assert_eq!(
+            Some("1.2.3"),
+            pattern.extract_version("release/v1.2.3-final")
+        );
+        assert_eq!(
+            Some("1.2.3-rc.1"),
+            pattern.extract_version("release/v1.2.3-rc.1-final")
+        );
+        assert_eq!(None, pattern.extract_version("release/v1.2.3"));
+        assert_eq!(None, pattern.extract_version("v1.2.3-final"));
+    }
+
+    #[test]
+    fn test_template_with_prefix_only() {
+        let pattern: TagPattern = "mycrate@{version}".parse().unwrap();
+
+        assert_eq!(Some("1.2.3"), pattern.extract_version("mycrate@1.2.3"));
+        assert_eq!(None, pattern.extract_version("othercrate@1.2.3"));
+    }
+
+    #[test]
+    fn test_regex_pattern() {
+        let pattern: TagPattern = "regex:^v(?P<version>[^-]+)-(linux|windows)$"
+            .parse()
+            .unwrap();
+
+        assert_eq!(Some("1.2.3"), pattern.extract_version("v1.2.3-linux"));
+        assert_eq!(Some("1.2.3"), pattern.extract_version("v1.2.3-windows"));
+        assert_eq!(None, pattern.extract_version("v1.2.3-macos"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!("v{version}-{version}".parse::<TagPattern>().is_err());
+        assert!("v1.2.3".parse::<TagPattern>().is_err());
+        assert!("regex:^v(.+)$".parse::<TagPattern>().is_err());
+        assert!("regex:^v(?P<version>.+$".parse::<TagPattern>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for pattern in &["v{version}-linux", "regex:^v(?P<version>.+)$"] {
+            assert_eq!(*pattern, pattern.parse::<TagPattern>().unwrap().to_string());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use git2::Oid;
+use regex::Regex;
+use toml_edit::{Document, Item};
+
+use crate::error::{MinverError, Result};
+use crate::metadata::MetadataContext;
+use crate::minver_core::{self, VersionTag};
+use crate::{MinverConfig, Version};
+
+/// The file that `cargo package` adds to packaged crates.
+const CARGO_VCS_INFO_FILE: &str = ".cargo_vcs_info.json";
+/// The file that `git archive` fills in when it has the `export-subst` attribute.
+const GIT_ARCHIVAL_FILE: &str = ".git_archival.txt";
+/// The key of the line in [`GIT_ARCHIVAL_FILE`] that contains the output of `git describe`.
+const DESCRIBE_KEY: &str = "describe-name:";
+
+/// Where a version was read from. Sources are tried in the order they are declared in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VersionSource {
+    /// The `version_override` configuration value (`MINVER_VERSION_OVERRIDE`).
+    Override,
+    /// The `Cargo.toml` of a crate packaged by `cargo package`, which is identified by its
+    /// `.cargo_vcs_info.json` file. The version in the manifest was already set when the crate
+    /// was packaged, so it is used even if the crate is vendored inside another repository.
+    CargoPackage,
+    /// The tags of a git repository.
+    Repository,
+    /// A `.git_archival.txt` file filled in by `git archive`.
+    ///
+    /// To use this source, commit a `.git_archival.txt` file containing
+    /// `describe-name: $Format:%(describe:tags=true)$` and add
+    /// `.git_archival.txt export-subst` to `.gitattributes`.
+    GitArchive,
+}
+
+/// A version along with the source it was read from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourcedVersion {
+    /// The version.
+    pub version: Version,
+    /// Where the version was read from.
+    pub source: VersionSource,
+}
+
+/// Gets the version of the project in `dir`, trying each [`VersionSource`] in order.
+///
+/// This makes it possible to build from a source tarball or a `git archive` export, which do not
+/// contain a repository. If no source has a version, the error from opening the repository is
+/// returned.
+pub fn get_version_from_sources(dir: &Path, config: &MinverConfig) -> Result<SourcedVersion> {
+    if let Some(version) = &config.version_override {
+        log::info!("Using version override {}", version);
+        return Ok(SourcedVersion {
+            version: version.clone(),
+            source: VersionSource::Override,
+        });
+    }
+
+    if let Some(version) = read_cargo_package_version(dir)? {
+        return Ok(SourcedVersion {
+            version,
+            source: VersionSource::CargoPackage,
+        });
+    }
+
+    match crate::open_repository(dir, config) {
+        Ok(repository) => Ok(SourcedVersion {
+            version: crate::get_version(&repository, config)?,
+            source: VersionSource::Repository,
+        }),
+        Err(e @ MinverError::RepositoryNotFound { .. }) => {
+            log::debug!("{}, trying other version sources", e);
+            match read_git_archive_version(dir, config)? {
+                Some(version) => Ok(SourcedVersion {
+                    version,
+                    source: VersionSource::GitArchive,
+                }),
+                None => {
+                    log::debug!("No version found in {:?}", dir);
+                    Err(e)
+                }
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the version from the manifest of a crate packaged by `cargo package`, if `dir` contains
+/// one.
+pub(crate) fn read_cargo_package_version(dir: &Path) -> Result<Option<Version>> {
+    if !dir.join(CARGO_VCS_INFO_FILE).is_file() {
+        return Ok(None);
+    }
+
+    let manifest_path = dir.join("Cargo.toml");
+    let document = read_file(&manifest_path)?
+        .parse::<Document>()
+        .map_err(|source| MinverError::InvalidManifest {
+            path: manifest_path.clone(),
+            source,
+        })?;
+    let version = document
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(Item::as_str);
+    match version {
+        Some(version) => {
+            log::info!("Using version {} of packaged crate", version);
+            Ok(Some(Version::parse(version)?))
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn read_git_archive_version(
+    dir: &Path,
+    config: &MinverConfig,
+) -> Result<Option<Version>> {
+    let path = dir.join(GIT_ARCHIVAL_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let contents = read_file(&path)?;
+    let describe = contents
+        .lines()
+        .find_map(|line| line.strip_prefix(DESCRIBE_KEY))
+        .or_else(|| contents.lines().find(|line| !line.trim().is_empty()))
+        .map(str::trim)
+        .unwrap_or_default();
+    if describe.is_empty() || describe.contains("$Format:") {
+        log::debug!("{:?} was not filled in by git archive, ignoring it", path);
+        return Ok(None);
+    }
+
+    // `git describe` output is either the tag name, or the tag name followed by the height and
+    // the abbreviated commit id, e.g. `1.2.3-4-g0123abc`
+    let re = Regex::new("^(?P<tag>.+)-(?P<height>\\d+)-g(?P<sha>[0-9a-f]+)$").unwrap();
+    let (tag_name, height, context) = match re.captures(describe) {
+        Some(captures) => (
+            captures.name("tag").unwrap().as_str(),
+            captures["height"]
+                .parse()
+                .map_err(|_| MinverError::InvalidVersion(String::from(describe)))?,
+            MetadataContext::with_short_sha(&captures["sha"]),
+        ),
+        None => (describe, 0, MetadataContext::default()),
+    };
+
+    let version = match config
+        .tag_patterns()
+        .iter()
+        .find_map(|pattern| pattern.extract_version(tag_name))
+    {
+        Some(version) => Version::parse(version)?,
+        None => {
+            log::warn!(
+                "Tag {} in {:?} does not match the tag pattern",
+                tag_name,
+                path
+            );
+            return Ok(None);
+        }
+    };
+
+    let tag = VersionTag {
+        name: String::from(tag_name),
+        version,
+        commit: Oid::zero(),
+    };
+    let calculation = minver_core::calculate_from_candidates(
+        vec![(tag, height)],
+        vec![],
+        height == 0,
+        &context,
+        &config.auto_increment_level.level(),
+        config,
+    )?;
+    log::info!(
+        "Using version {} from git archive description {}",
+        calculation.version,
+        describe
+    );
+    Ok(Some(calculation.version))
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| MinverError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+impl fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            VersionSource::Override => "version override",
+            VersionSource::Repository => "git repository",
+            VersionSource::CargoPackage => "packaged crate",
+            VersionSource::GitArchive => "git archive",
+        };
+        write!(f, "{}", name)
+    }
+}
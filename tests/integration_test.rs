@@ -289,7 +289,7 @@ fn test_configured_version_is_incremented() {
     repo_test_helper::commit_on_head(&repo, "m2").unwrap();
 
     let config = MinverConfig {
-        auto_increment_level: SemVerLevel::Minor,
+        auto_increment_level: IncrementLevel::Fixed(SemVerLevel::Minor),
         ..MinverConfig::default()
     };
 
@@ -305,7 +305,7 @@ fn test_configured_version_is_incremented() {
     );
 
     let config = MinverConfig {
-        auto_increment_level: SemVerLevel::Major,
+        auto_increment_level: IncrementLevel::Fixed(SemVerLevel::Major),
         ..MinverConfig::default()
     };
 
@@ -446,3 +446,1977 @@ fn test_invalid_tags_are_ignored() {
         minver_rs::get_version(&repo, &MinverConfig::default()).unwrap()
     );
 }
+
+#[test]
+fn test_version_below_minimum_major_minor_is_raised_to_minimum() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.4.2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+
+    let config = MinverConfig {
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 2,
+            minor: 0,
+            patch: 0,
+            prerelease: Some(String::from("alpha.0.2")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_version_above_minimum_major_minor_is_unchanged() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "2.1.0").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+
+    let config = MinverConfig {
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 2,
+            minor: 1,
+            patch: 1,
+            prerelease: Some(String::from("alpha.1")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_tagged_head_ignores_minimum_major_minor() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.4.2").unwrap();
+
+    let config = MinverConfig {
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 4,
+            patch: 2,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_minimum_major_minor_is_used_when_no_tags_are_present() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+
+    let config = MinverConfig {
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 2,
+            minor: 0,
+            patch: 0,
+            prerelease: Some(String::from("alpha.0")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_when_height_is_ignored_then_tag_version_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+
+    let config = MinverConfig {
+        ignore_height: true,
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_when_height_is_ignored_then_tag_prerelease_is_unchanged() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3-rc.1").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+
+    let config = MinverConfig {
+        ignore_height: true,
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: Some(String::from("rc.1")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_version_at_revision_is_calculated_without_checkout() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let intermediate_commit = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+    repo_test_helper::tag_head(&repo, "1.3.0").unwrap();
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 4,
+            prerelease: Some(String::from("alpha.1")),
+            build_metadata: None
+        },
+        minver_rs::get_version_at(
+            &repo,
+            &intermediate_commit.id().to_string(),
+            &MinverConfig::default()
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version_at(&repo, "1.2.3", &MinverConfig::default()).unwrap()
+    );
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 3,
+            patch: 0,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &MinverConfig::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_when_revision_does_not_exist_then_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+
+    let err = minver_rs::get_version_at(&repo, "does-not-exist", &MinverConfig::default()).err();
+    assert!(matches!(err, Some(MinverError::RevisionNotFound { .. })));
+}
+
+#[test]
+fn test_version_history_matches_version_of_each_commit() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &commit_1, "1.2.3").unwrap();
+
+    let branch_1_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "b1c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &branch_1_commit_1, "1.3.0-beta").unwrap();
+    let branch_1_commit_2 =
+        repo_test_helper::commit_with_parent(&repo, &branch_1_commit_1, "b1c2").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &commit_1).unwrap();
+    let branch_2_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "b2c1").unwrap();
+
+    repo_test_helper::merge_commit(&repo, &[&branch_2_commit_1, &branch_1_commit_2], "m").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+
+    let config = MinverConfig::default();
+    let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+
+    assert_eq!(7, history.len());
+    for entry in &history {
+        assert_eq!(
+            minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+            entry.version
+        );
+    }
+
+    let head = &history[0];
+    assert_eq!(Some(String::from("1.3.0-beta")), head.base_tag);
+    assert_eq!(3, head.height);
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 3,
+            patch: 1,
+            prerelease: Some(String::from("beta.3")),
+            build_metadata: None
+        },
+        head.version
+    );
+}
+
+#[test]
+fn test_version_history_range_excludes_older_commits() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0").unwrap();
+    let commit_2 = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    let commit_3 = repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+
+    let history =
+        minver_rs::get_version_history(&repo, "1.0.0..HEAD", &MinverConfig::default()).unwrap();
+
+    assert_eq!(
+        vec![
+            HistoryEntry {
+                commit: commit_3.id(),
+                version: Version {
+                    major: 1,
+                    minor: 0,
+                    patch: 1,
+                    prerelease: Some(String::from("alpha.2")),
+                    build_metadata: None
+                },
+                base_tag: Some(String::from("1.0.0")),
+                height: 2
+            },
+            HistoryEntry {
+                commit: commit_2.id(),
+                version: Version {
+                    major: 1,
+                    minor: 0,
+                    patch: 1,
+                    prerelease: Some(String::from("alpha.1")),
+                    build_metadata: None
+                },
+                base_tag: Some(String::from("1.0.0")),
+                height: 1
+            },
+        ],
+        history
+    );
+}
+
+#[test]
+fn test_detailed_version_reports_base_tag_and_candidates() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+
+    let branch_1_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "b1c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &branch_1_commit_1, "1.3.0").unwrap();
+    let branch_1_commit_2 =
+        repo_test_helper::commit_with_parent(&repo, &branch_1_commit_1, "b1c2").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &commit_1).unwrap();
+    let branch_2_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "b2c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &branch_2_commit_1, "1.2.3").unwrap();
+
+    repo_test_helper::merge_commit(&repo, &[&branch_2_commit_1, &branch_1_commit_2], "m").unwrap();
+
+    let calculation = minver_rs::get_version_detailed(&repo, &MinverConfig::default()).unwrap();
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 3,
+            patch: 1,
+            prerelease: Some(String::from("alpha.2")),
+            build_metadata: None
+        },
+        calculation.version
+    );
+    let base_tag = calculation.base_tag.unwrap();
+    assert_eq!("1.3.0", base_tag.name);
+    assert_eq!(branch_1_commit_1.id(), base_tag.commit);
+    assert_eq!(2, calculation.height);
+    assert_eq!(Some(SemVerLevel::Patch), calculation.incremented_level);
+
+    let mut candidates: Vec<(String, u32)> = calculation
+        .candidates
+        .into_iter()
+        .map(|(tag, height)| (tag.name, height))
+        .collect();
+    candidates.sort();
+    assert_eq!(
+        vec![(String::from("1.2.3"), 1), (String::from("1.3.0"), 2)],
+        candidates
+    );
+
+    let options: Vec<&str> = calculation
+        .config_influences
+        .iter()
+        .map(|influence| influence.option)
+        .collect();
+    assert_eq!(
+        vec!["prerelease_identifier", "auto_increment_level"],
+        options
+    );
+}
+
+#[test]
+fn test_detailed_version_of_tagged_commit_is_not_incremented() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let calculation = minver_rs::get_version_detailed(&repo, &MinverConfig::default()).unwrap();
+
+    assert_eq!(0, calculation.height);
+    assert_eq!(None, calculation.incremented_level);
+    assert!(calculation.config_influences.is_empty());
+}
+
+#[test]
+fn test_when_head_is_unborn_then_unborn_head_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+
+    let err = minver_rs::get_version(&repo, &MinverConfig::default()).err();
+    assert!(matches!(err, Some(MinverError::UnbornHead)));
+}
+
+#[test]
+fn test_when_directory_is_not_a_repository_then_repository_not_found_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+
+    let err = minver_rs::open_repository(dir.path(), &config).err();
+    assert!(matches!(err, Some(MinverError::RepositoryNotFound { .. })));
+}
+
+#[test]
+fn test_when_manifest_has_no_package_table_then_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+
+    std::fs::write(dir.path().join("Cargo.toml"), "[workspace]\nmembers = []\n").unwrap();
+
+    let err = build_utils::update_package_version(
+        &dir.path().as_os_str().to_os_string(),
+        &MinverConfig::default(),
+    )
+    .err();
+    assert!(matches!(err, Some(MinverError::MissingPackageTable { .. })));
+}
+
+#[test]
+fn test_when_commit_has_multiple_tags_then_highest_precedence_tag_is_used() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0-rc.1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0-rc.2").unwrap();
+
+    let calculation = minver_rs::get_version_detailed(&repo, &MinverConfig::default()).unwrap();
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 0,
+            patch: 0,
+            prerelease: None,
+            build_metadata: None
+        },
+        calculation.version
+    );
+    assert_eq!("1.0.0", calculation.base_tag.unwrap().name);
+
+    let discarded: Vec<String> = calculation
+        .discarded_tags
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+    assert_eq!(
+        vec![String::from("1.0.0-rc.2"), String::from("1.0.0-rc.1")],
+        discarded
+    );
+}
+
+#[test]
+fn test_when_commit_has_tags_with_equal_precedence_then_tag_is_chosen_by_name() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0+b").unwrap();
+    repo_test_helper::tag_head(&repo, "1.0.0+a").unwrap();
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 0,
+            patch: 0,
+            prerelease: None,
+            build_metadata: Some(String::from("a"))
+        },
+        minver_rs::get_version(&repo, &MinverConfig::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_repository_is_discovered_from_subdirectory() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let subdirectory = dir.path().join("crates").join("my_crate");
+    std::fs::create_dir_all(&subdirectory).unwrap();
+
+    let discovered = minver_rs::open_repository(&subdirectory, &MinverConfig::default()).unwrap();
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&discovered, &MinverConfig::default()).unwrap()
+    );
+
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+    let err = minver_rs::open_repository(&subdirectory, &config).err();
+    assert!(matches!(err, Some(MinverError::RepositoryNotFound { .. })));
+}
+
+#[test]
+fn test_repository_is_discovered_from_linked_worktree() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let worktree_dir = TempDir::new().unwrap();
+    let worktree_path = worktree_dir.path().join("worktree");
+    repo.worktree("worktree", &worktree_path, None).unwrap();
+
+    let discovered = minver_rs::open_repository(&worktree_path, &MinverConfig::default()).unwrap();
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&discovered, &MinverConfig::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_rerun_if_changed_uses_files_of_discovered_repository() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir(&crate_dir).unwrap();
+
+    let config = MinverConfig::default();
+    let paths = build_utils::get_rerun_if_changed(&crate_dir.into_os_string(), &config);
+    let git_dir = repo.path();
+    let branch = repo.find_reference("HEAD").unwrap();
+    assert_eq!(
+        vec![
+            git_dir.join("HEAD"),
+            git_dir.join(branch.symbolic_target().unwrap()),
+            git_dir.join("refs").join("tags"),
+        ],
+        paths
+    );
+
+    let other_dir = TempDir::new().unwrap();
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+    assert!(build_utils::get_rerun_if_changed(
+        &other_dir.path().as_os_str().to_os_string(),
+        &config
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_package_version_is_updated_for_crate_in_subdirectory() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    std::fs::write(
+        crate_dir.join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+
+    build_utils::update_package_version(
+        &crate_dir.as_os_str().to_os_string(),
+        &MinverConfig::default(),
+    )
+    .unwrap();
+
+    let manifest = std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("version = \"1.2.3\""));
+}
+
+#[test]
+fn test_tags_matching_pattern_with_suffix_are_used() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "release/v1.2.3-final").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::tag_head(&repo, "2.0.0").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+
+    let config = MinverConfig {
+        tag_pattern: vec!["release/v{version}-final".parse().unwrap()],
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 4,
+            prerelease: Some(String::from("alpha.2")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_tags_matching_any_of_multiple_patterns_are_used() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "v1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::tag_head(&repo, "mycrate@1.3.0").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+    repo_test_helper::tag_head(&repo, "othercrate@2.0.0").unwrap();
+
+    let config = MinverConfig {
+        tag_pattern: vec![
+            "mycrate@{version}".parse().unwrap(),
+            "regex:^v(?P<version>.+)$".parse().unwrap(),
+        ],
+        ..MinverConfig::default()
+    };
+
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 3,
+            patch: 1,
+            prerelease: Some(String::from("alpha.1")),
+            build_metadata: None
+        },
+        calculation.version
+    );
+    assert_eq!("mycrate@1.3.0", calculation.base_tag.unwrap().name);
+}
+
+#[test]
+fn test_commits_not_matching_path_filter_do_not_increase_height() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_file_on_head(&repo, "a/lib.rs", "1", "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "a/lib.rs", "2", "m2").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "b/lib.rs", "1", "m3").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "a/src/main.rs", "1", "m4").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "b/lib.rs", "2", "m5").unwrap();
+
+    let config = MinverConfig {
+        path_filter: vec![String::from("a")],
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 4,
+            prerelease: Some(String::from("alpha.2")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+
+    let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+    for entry in &history {
+        assert_eq!(
+            minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+            entry.version
+        );
+    }
+}
+
+#[test]
+fn test_when_path_filter_is_unchanged_since_tag_then_tag_version_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_file_on_head(&repo, "a/lib.rs", "1", "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "b/lib.rs", "1", "m2").unwrap();
+
+    let config = MinverConfig {
+        path_filter: vec![String::from("a/*.rs")],
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            prerelease: None,
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+
+    // HEAD itself is not tagged, so the minimum still applies
+    let config = MinverConfig {
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..config
+    };
+    assert_eq!(
+        "2.0.0-alpha.0",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_package_scoped_build_util_uses_package_directory_and_name() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let manifest = "[package]\nname = \"foo\"\nversion = \"0.0.0\"\n";
+    repo_test_helper::commit_file_on_head(&repo, "crates/foo/Cargo.toml", manifest, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "foo-v1.0.0").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "crates/bar/lib.rs", "1", "m2").unwrap();
+    repo_test_helper::tag_head(&repo, "bar-v2.0.0").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "crates/foo/lib.rs", "1", "m3").unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "crates/bar/lib.rs", "2", "m4").unwrap();
+
+    let config = MinverConfig {
+        package_scoped: true,
+        ..MinverConfig::default()
+    };
+    let crate_dir = dir.path().join("crates").join("foo");
+    build_utils::update_package_version(&crate_dir.as_os_str().to_os_string(), &config).unwrap();
+
+    let manifest = std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("version = \"1.0.1-alpha.1\""));
+}
+
+#[test]
+fn test_when_first_parent_is_set_then_tags_on_merged_branches_are_ignored() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &commit_1, "1.2.3").unwrap();
+
+    let main_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "mc1").unwrap();
+    let main_commit_2 = repo_test_helper::commit_with_parent(&repo, &main_commit_1, "mc2").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &commit_1).unwrap();
+    let feature_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "fc1").unwrap();
+    repo_test_helper::tag_commit(&repo, &feature_commit_1, "2.0.0").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &main_commit_2).unwrap();
+    repo_test_helper::merge_commit(&repo, &[&main_commit_2, &feature_commit_1], "m").unwrap();
+
+    assert_eq!(
+        Version {
+            major: 2,
+            minor: 0,
+            patch: 1,
+            prerelease: Some(String::from("alpha.1")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &MinverConfig::default()).unwrap()
+    );
+
+    let config = MinverConfig {
+        first_parent: true,
+        ..MinverConfig::default()
+    };
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 4,
+            prerelease: Some(String::from("alpha.3")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&repo, &config).unwrap()
+    );
+
+    let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+    assert!(history
+        .iter()
+        .all(|entry| entry.commit != feature_commit_1.id()));
+    for entry in &history {
+        assert_eq!(
+            minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+            entry.version
+        );
+    }
+}
+
+#[test]
+fn test_height_modes_after_merging_a_shorter_branch() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &commit_1, "1.2.3").unwrap();
+
+    let main_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "mc1").unwrap();
+    let main_commit_2 = repo_test_helper::commit_with_parent(&repo, &main_commit_1, "mc2").unwrap();
+    let main_commit_3 = repo_test_helper::commit_with_parent(&repo, &main_commit_2, "mc3").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &commit_1).unwrap();
+    let feature_commit_1 = repo_test_helper::commit_with_parent(&repo, &commit_1, "fc1").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &main_commit_3).unwrap();
+    repo_test_helper::merge_commit(&repo, &[&main_commit_3, &feature_commit_1], "m").unwrap();
+
+    let heights: Vec<(HeightMode, u32, u32)> = vec![
+        (HeightMode::ShortestPath, 3, 2),
+        (HeightMode::FirstParent, 3, 4),
+        (HeightMode::CommitCount, 3, 5),
+    ];
+    for (height_mode, before_merge, after_merge) in heights {
+        let config = MinverConfig {
+            height_mode,
+            ..MinverConfig::default()
+        };
+
+        let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+        assert_eq!(after_merge, calculation.height, "{}", height_mode);
+        assert_eq!(height_mode, calculation.height_mode);
+        assert_eq!(
+            before_merge,
+            minver_rs::get_version_detailed_at(&repo, &main_commit_3.id().to_string(), &config)
+                .unwrap()
+                .height,
+            "{}",
+            height_mode
+        );
+
+        let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+        for entry in &history {
+            assert_eq!(
+                minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+                entry.version
+            );
+        }
+    }
+}
+
+#[test]
+fn test_when_height_mode_is_set_then_it_is_recorded_as_an_influence() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+
+    let config = MinverConfig {
+        height_mode: HeightMode::CommitCount,
+        ..MinverConfig::default()
+    };
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+
+    assert_eq!(1, calculation.height);
+    assert!(calculation
+        .config_influences
+        .iter()
+        .any(|influence| influence.to_string() == "height_mode = commit_count"));
+}
+
+#[test]
+fn test_when_shallow_history_ends_before_a_tag_then_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+    let commit_3 = repo_test_helper::commit_on_head(&repo, "c3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c4").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 2).unwrap();
+
+    match minver_rs::get_version(&clone, &MinverConfig::default()) {
+        Err(MinverError::ShallowRepository { commit }) => assert_eq!(commit_3.id(), commit),
+        result => panic!("Expected ShallowRepository error, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_when_shallow_policy_is_warn_then_default_version_is_returned() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c3").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 2).unwrap();
+
+    let config = MinverConfig {
+        shallow_policy: ShallowPolicy::Warn,
+        ..MinverConfig::default()
+    };
+    let calculation = minver_rs::get_version_detailed(&clone, &config).unwrap();
+
+    assert_eq!(None, calculation.base_tag);
+    assert_eq!(
+        Version {
+            major: 0,
+            minor: 0,
+            patch: 0,
+            prerelease: Some(String::from("alpha.0")),
+            build_metadata: None
+        },
+        calculation.version
+    );
+}
+
+#[test]
+fn test_when_shallow_policy_is_limit_then_height_is_measured_to_boundary() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c4").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 3).unwrap();
+
+    let config = MinverConfig {
+        shallow_policy: ShallowPolicy::Limit,
+        ..MinverConfig::default()
+    };
+    let calculation = minver_rs::get_version_detailed(&clone, &config).unwrap();
+
+    assert_eq!(2, calculation.height);
+    assert_eq!(
+        Version {
+            major: 0,
+            minor: 0,
+            patch: 1,
+            prerelease: Some(String::from("alpha.0.2")),
+            build_metadata: None
+        },
+        calculation.version
+    );
+
+    let history = minver_rs::get_version_history(&clone, "HEAD", &config).unwrap();
+    assert_eq!(calculation.version, history[0].version);
+}
+
+#[test]
+fn test_shallow_clone_history_is_walked_up_to_boundary() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+    repo_test_helper::commit_on_head(&repo, "feat: c3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c4").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 3).unwrap();
+
+    for height_mode in &[HeightMode::CommitCount, HeightMode::FirstParent] {
+        let config = MinverConfig {
+            shallow_policy: ShallowPolicy::Limit,
+            height_mode: *height_mode,
+            auto_increment_level: IncrementLevel::Auto,
+            ..MinverConfig::default()
+        };
+        let calculation = minver_rs::get_version_detailed(&clone, &config).unwrap();
+        assert_eq!(2, calculation.height, "{}", height_mode);
+        assert_eq!(
+            "0.1.0-alpha.0.2",
+            calculation.version.to_string(),
+            "{}",
+            height_mode
+        );
+
+        let history = minver_rs::get_version_history(&clone, "HEAD", &config).unwrap();
+        assert_eq!(3, history.len());
+        assert_eq!(calculation.version, history[0].version);
+
+        let config = MinverConfig {
+            shallow_policy: ShallowPolicy::Error,
+            ..config
+        };
+        match minver_rs::get_version(&clone, &config) {
+            Err(MinverError::ShallowRepository { .. }) => {}
+            result => panic!("Expected ShallowRepository error, got {:?}", result),
+        }
+        match minver_rs::get_version_history(&clone, "HEAD", &config) {
+            Err(MinverError::ShallowRepository { .. }) => {}
+            result => panic!("Expected ShallowRepository error, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_shallow_clone_boundary_does_not_count_as_tag_for_minimum_major_minor() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 1).unwrap();
+
+    let config = MinverConfig {
+        shallow_policy: ShallowPolicy::Limit,
+        minimum_major_minor: Some(MajorMinor { major: 2, minor: 0 }),
+        ..MinverConfig::default()
+    };
+    assert_eq!(
+        "2.0.0-alpha.0",
+        minver_rs::get_version(&clone, &config).unwrap().to_string()
+    );
+    let history = minver_rs::get_version_history(&clone, "HEAD", &config).unwrap();
+    assert_eq!("2.0.0-alpha.0", history[0].version.to_string());
+}
+
+#[test]
+fn test_when_tag_is_within_shallow_history_then_tag_version_is_used() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c2").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c3").unwrap();
+    let clone_dir = TempDir::new().unwrap();
+    let clone = repo_test_helper::clone_shallow(&repo, clone_dir.path(), 2).unwrap();
+
+    assert_eq!(
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 4,
+            prerelease: Some(String::from("alpha.1")),
+            build_metadata: None
+        },
+        minver_rs::get_version(&clone, &MinverConfig::default()).unwrap()
+    );
+}
+
+#[test]
+fn test_ci_tag_is_only_used_when_head_is_the_tagged_commit() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let previous = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    let head = repo_test_helper::commit_on_head(&repo, "m3").unwrap();
+
+    // The variables are only set for the CLI, since tests run in parallel in this process
+    let minver = |sha: git2::Oid, args: &[&str], vars: &[(&str, &str)]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_minver"))
+            .args(args)
+            .current_dir(dir.path())
+            .env_clear()
+            .env("GITHUB_REF", "refs/tags/5.0.0")
+            .env("GITHUB_SHA", sha.to_string())
+            .envs(vars.iter().cloned())
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let previous_rev = previous.id().to_string();
+
+    assert_eq!("1.2.4-alpha.2\n", minver(previous.id(), &[], &[]));
+    assert_eq!(
+        "1.2.4-alpha.1\n",
+        minver(previous.id(), &["--rev", &previous_rev], &[])
+    );
+
+    assert_eq!("5.0.0\n", minver(head.id(), &[], &[]));
+    assert_eq!(
+        "1.2.4-alpha.1\n",
+        minver(head.id(), &["--rev", &previous_rev], &[])
+    );
+    assert!(!minver(head.id(), &["history"], &[]).contains("5.0.0"));
+    assert_eq!(
+        "1.2.4-alpha.2\n",
+        minver(head.id(), &[], &[("MINVER_CI_TAG_DETECTION", "false")])
+    );
+}
+
+#[test]
+fn test_version_override_takes_precedence_over_repository() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let config = MinverConfig {
+        version_override: Some("2.0.0-beta.1".parse().unwrap()),
+        ..MinverConfig::default()
+    };
+    let sourced = minver_rs::get_version_from_sources(dir.path(), &config).unwrap();
+
+    assert_eq!(VersionSource::Override, sourced.source);
+    assert_eq!("2.0.0-beta.1", sourced.version.to_string());
+
+    let sourced =
+        minver_rs::get_version_from_sources(dir.path(), &MinverConfig::default()).unwrap();
+    assert_eq!(VersionSource::Repository, sourced.source);
+    assert_eq!("1.2.3", sourced.version.to_string());
+}
+
+#[test]
+fn test_packaged_crate_uses_version_from_manifest() {
+    let dir = TempDir::new().unwrap();
+    let manifest = "[package]\nname = \"my_crate\"\nversion = \"1.2.3\"\n";
+    std::fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+    std::fs::write(
+        dir.path().join(".cargo_vcs_info.json"),
+        "{\"git\":{\"sha1\":\"0123456789abcdef0123456789abcdef01234567\"}}",
+    )
+    .unwrap();
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+
+    let sourced = minver_rs::get_version_from_sources(dir.path(), &config).unwrap();
+    assert_eq!(VersionSource::CargoPackage, sourced.source);
+    assert_eq!("1.2.3", sourced.version.to_string());
+
+    build_utils::update_package_version(&dir.path().as_os_str().to_os_string(), &config).unwrap();
+    assert_eq!(
+        manifest,
+        std::fs::read_to_string(dir.path().join("Cargo.toml")).unwrap()
+    );
+}
+
+#[test]
+fn test_vendored_packaged_crate_uses_version_from_manifest() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "5.0.0").unwrap();
+    let crate_dir = dir.path().join("vendor").join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    let manifest = "[package]\nname = \"my_crate\"\nversion = \"1.2.3\"\n";
+    std::fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+    std::fs::write(
+        crate_dir.join(".cargo_vcs_info.json"),
+        "{\"git\":{\"sha1\":\"0123456789abcdef0123456789abcdef01234567\"}}",
+    )
+    .unwrap();
+    let config = MinverConfig::default();
+
+    let sourced = minver_rs::get_version_from_sources(&crate_dir, &config).unwrap();
+    assert_eq!(VersionSource::CargoPackage, sourced.source);
+    assert_eq!("1.2.3", sourced.version.to_string());
+
+    build_utils::update_package_version(&crate_dir.as_os_str().to_os_string(), &config).unwrap();
+    assert_eq!(
+        manifest,
+        std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap()
+    );
+    let build_info =
+        build_utils::get_build_info(&crate_dir.as_os_str().to_os_string(), &config).unwrap();
+    assert_eq!("1.2.3", build_info.version.to_string());
+}
+
+#[test]
+fn test_git_archive_uses_version_from_description() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join(".git_archival.txt"),
+        "node: 0123456789abcdef0123456789abcdef01234567\ndescribe-name: v1.2.3-4-g0123456\n",
+    )
+    .unwrap();
+    let config = MinverConfig {
+        discover_repository: false,
+        tag_prefix: String::from("v"),
+        ..MinverConfig::default()
+    };
+
+    let sourced = minver_rs::get_version_from_sources(dir.path(), &config).unwrap();
+    assert_eq!(VersionSource::GitArchive, sourced.source);
+    assert_eq!("1.2.4-alpha.4", sourced.version.to_string());
+
+    build_utils::update_package_version(&dir.path().as_os_str().to_os_string(), &config).unwrap();
+    let manifest = std::fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("version = \"1.2.4-alpha.4\""));
+}
+
+#[test]
+fn test_when_archive_file_is_not_filled_in_then_repository_error_is_returned() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(".git_archival.txt"),
+        "describe-name: $Format:%(describe:tags=true)$\n",
+    )
+    .unwrap();
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+
+    let err = minver_rs::get_version_from_sources(dir.path(), &config).err();
+    assert!(matches!(err, Some(MinverError::RepositoryNotFound { .. })));
+}
+
+#[test]
+fn test_when_working_tree_is_dirty_then_dirty_marker_is_added() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "file.txt", "a", "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let config = MinverConfig {
+        dirty_marker: Some("+dirty".parse().unwrap()),
+        ..MinverConfig::default()
+    };
+
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert!(!calculation.is_dirty);
+    assert_eq!("1.2.3", calculation.version.to_string());
+
+    std::fs::write(dir.path().join("file.txt"), "b").unwrap();
+
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert!(calculation.is_dirty);
+    assert_eq!("1.2.3+dirty", calculation.version.to_string());
+    assert_eq!(
+        "1.2.3+dirty",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+    assert_eq!(
+        "1.2.3",
+        minver_rs::get_version_at(&repo, "HEAD", &config)
+            .unwrap()
+            .to_string()
+    );
+
+    // Without a marker, the working tree is only checked for the detailed calculation
+    let calculation = minver_rs::get_version_detailed(&repo, &MinverConfig::default()).unwrap();
+    assert!(calculation.is_dirty);
+    assert_eq!("1.2.3", calculation.version.to_string());
+}
+
+#[test]
+fn test_when_dirty_ignore_untracked_is_set_then_untracked_files_are_ignored() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_file_on_head(&repo, "file.txt", "a", "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    std::fs::write(dir.path().join("untracked.txt"), "a").unwrap();
+
+    let config = MinverConfig {
+        dirty_marker: Some("-dirty".parse().unwrap()),
+        ..MinverConfig::default()
+    };
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert!(calculation.is_dirty);
+    assert_eq!("1.2.4-dirty", calculation.version.to_string());
+
+    let config = MinverConfig {
+        dirty_ignore_untracked: true,
+        ..config
+    };
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert!(!calculation.is_dirty);
+    assert_eq!("1.2.3", calculation.version.to_string());
+}
+
+#[test]
+fn test_build_metadata_placeholders_are_replaced() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "v1.2.3").unwrap();
+    let commit = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    let short_sha = commit.as_object().short_id().unwrap();
+    let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+    let config = MinverConfig {
+        tag_prefix: String::from("v"),
+        build_metadata: Some(String::from("{branch}.{height}.g{short_sha}.{tag}")),
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        format!(
+            "1.2.4-alpha.1+{}.1.g{}.v1-2-3",
+            branch,
+            short_sha.as_str().unwrap()
+        ),
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+    assert_eq!(
+        format!(
+            "1.2.4-alpha.1+detached.1.g{}.v1-2-3",
+            short_sha.as_str().unwrap()
+        ),
+        minver_rs::get_version_at(&repo, &commit.id().to_string(), &config)
+            .unwrap()
+            .to_string()
+    );
+
+    let config = MinverConfig {
+        build_metadata: Some(String::from("{sha}")),
+        ..config
+    };
+    assert_eq!(
+        Some(commit.id().to_string()),
+        minver_rs::get_version(&repo, &config)
+            .unwrap()
+            .build_metadata
+    );
+}
+
+#[test]
+fn test_branch_rules_choose_prerelease_identifier() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let commit_2 = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo.branch("release/1.2", &commit_2, false).unwrap();
+    repo.branch("feature/my_change", &commit_2, false).unwrap();
+    repo.branch("other", &commit_1, false).unwrap();
+
+    let config = MinverConfig {
+        branch_rules: vec![
+            "release/*=rc".parse().unwrap(),
+            "feature/*=feat-{branch_slug}".parse().unwrap(),
+            "HEAD=ci".parse().unwrap(),
+        ],
+        ..MinverConfig::default()
+    };
+
+    repo.set_head("refs/heads/release/1.2").unwrap();
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert_eq!("1.2.4-rc.1", calculation.version.to_string());
+    assert!(calculation
+        .config_influences
+        .iter()
+        .any(|influence| influence.to_string() == "branch_rules = release/*=rc"));
+
+    repo.set_head("refs/heads/feature/my_change").unwrap();
+    assert_eq!(
+        "1.2.4-feat-feature-my-change.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+
+    repo_test_helper::checkout_commit(&repo, &commit_2).unwrap();
+    assert_eq!(
+        "1.2.4-ci.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+
+    assert_eq!(
+        "1.2.4-rc.1",
+        minver_rs::get_version_at(&repo, "release/1.2", &config)
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(
+        "1.2.3",
+        minver_rs::get_version_at(&repo, "other", &config)
+            .unwrap()
+            .to_string()
+    );
+}
+
+#[test]
+fn test_when_no_branch_rule_matches_then_prerelease_identifier_is_used() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let commit_2 = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    repo_test_helper::checkout_commit(&repo, &commit_2).unwrap();
+
+    let config = MinverConfig {
+        branch_rules: vec!["main=beta".parse().unwrap()],
+        ..MinverConfig::default()
+    };
+
+    assert_eq!(
+        "1.2.4-alpha.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_auto_increment_level_uses_conventional_commits() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "feat!: initial").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let config = MinverConfig {
+        auto_increment_level: IncrementLevel::Auto,
+        ..MinverConfig::default()
+    };
+
+    repo_test_helper::commit_on_head(&repo, "chore: tidy up").unwrap();
+    assert_eq!(
+        "1.2.4-alpha.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+
+    repo_test_helper::commit_on_head(&repo, "fix: handle empty input").unwrap();
+    repo_test_helper::commit_on_head(&repo, "feat(parser): add arrays").unwrap();
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert_eq!("1.3.0-alpha.3", calculation.version.to_string());
+    assert_eq!(Some(SemVerLevel::Minor), calculation.incremented_level);
+
+    repo_test_helper::commit_on_head(&repo, "refactor: rename\n\nBREAKING CHANGE: renamed")
+        .unwrap();
+    assert_eq!(
+        "2.0.0-alpha.4",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+
+    let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+    for entry in &history {
+        assert_eq!(
+            minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+            entry.version
+        );
+    }
+}
+
+#[test]
+fn test_auto_increment_level_before_1_0_increments_minor_for_breaking_changes() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "0.3.0").unwrap();
+    repo_test_helper::commit_on_head(&repo, "feat!: remove old api").unwrap();
+
+    let config = MinverConfig {
+        auto_increment_level: IncrementLevel::Auto,
+        commit_type_levels: vec!["perf=Patch".parse().unwrap()],
+        ..MinverConfig::default()
+    };
+    assert_eq!(
+        "0.4.0-alpha.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+
+    let config = MinverConfig {
+        auto_initial_development: false,
+        ..config
+    };
+    assert_eq!(
+        "1.0.0-alpha.1",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_bump_trailer_overrides_increment_level() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2\n\nVersion-Bump: minor").unwrap();
+    let trigger = repo_test_helper::commit_on_head(&repo, "m3\n\nversion-bump: MAJOR")
+        .unwrap()
+        .id();
+    repo_test_helper::commit_on_head(&repo, "feat: m4\n\nVersion-Bump: patch").unwrap();
+
+    let config = MinverConfig {
+        bump_trailers: vec![String::from("Version-Bump")],
+        ..MinverConfig::default()
+    };
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert_eq!("2.0.0-alpha.3", calculation.version.to_string());
+    assert_eq!(Some(SemVerLevel::Major), calculation.incremented_level);
+    assert_eq!(Some(trigger), calculation.increment_commit);
+    assert!(calculation
+        .config_influences
+        .iter()
+        .any(|influence| influence.option == "bump_trailers"));
+    assert!(calculation
+        .config_influences
+        .iter()
+        .any(|influence| influence.option == "auto_increment_level" && influence.value == "Patch"));
+
+    // Trailers are ignored by default
+    let config = MinverConfig::default();
+    let calculation = minver_rs::get_version_detailed(&repo, &config).unwrap();
+    assert_eq!("1.2.4-alpha.3", calculation.version.to_string());
+    assert_eq!(None, calculation.increment_commit);
+
+    let config = MinverConfig {
+        bump_trailers: vec![String::from("Semver")],
+        auto_increment_level: IncrementLevel::Auto,
+        ..MinverConfig::default()
+    };
+    assert_eq!(
+        "1.3.0-alpha.3",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+    repo_test_helper::commit_on_head(&repo, "m5\n\nSemver: major").unwrap();
+    assert_eq!(
+        "2.0.0-alpha.4",
+        minver_rs::get_version(&repo, &config).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_version_history_matches_requested_level_of_each_commit() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+
+    let commit_1 = repo_test_helper::commit_on_head(&repo, "c1").unwrap();
+    repo_test_helper::tag_commit(&repo, &commit_1, "1.2.3").unwrap();
+    let commit_2 = repo_test_helper::commit_on_head(&repo, "fix: c2").unwrap();
+
+    let branch_1_commit_1 =
+        repo_test_helper::commit_with_parent(&repo, &commit_2, "feat: b1c1").unwrap();
+    let branch_1_commit_2 =
+        repo_test_helper::commit_with_parent(&repo, &branch_1_commit_1, "b1c2").unwrap();
+
+    repo_test_helper::checkout_commit(&repo, &commit_2).unwrap();
+    let branch_2_commit_1 =
+        repo_test_helper::commit_with_parent(&repo, &commit_2, "b2c1\n\nSemver: major").unwrap();
+    repo_test_helper::tag_commit(&repo, &branch_2_commit_1, "1.2.4-beta").unwrap();
+    let branch_2_commit_2 =
+        repo_test_helper::commit_with_parent(&repo, &branch_2_commit_1, "fix: b2c2").unwrap();
+
+    repo_test_helper::merge_commit(&repo, &[&branch_2_commit_2, &branch_1_commit_2], "m").unwrap();
+    repo_test_helper::commit_on_head(&repo, "c3").unwrap();
+
+    let config = MinverConfig {
+        auto_increment_level: IncrementLevel::Auto,
+        auto_initial_development: false,
+        bump_trailers: vec![String::from("Semver")],
+        ..MinverConfig::default()
+    };
+    let ranges = ["HEAD", &format!("{}..HEAD", branch_1_commit_1.id())];
+    for range in ranges.iter() {
+        let history = minver_rs::get_version_history(&repo, range, &config).unwrap();
+        for entry in &history {
+            assert_eq!(
+                minver_rs::get_version_at(&repo, &entry.commit.to_string(), &config).unwrap(),
+                entry.version
+            );
+        }
+    }
+
+    let history = minver_rs::get_version_history(&repo, "HEAD", &config).unwrap();
+    let branch_1_entry = history
+        .iter()
+        .find(|entry| entry.commit == branch_1_commit_2.id())
+        .unwrap();
+    assert_eq!("1.3.0-alpha.3", branch_1_entry.version.to_string());
+    assert_eq!("1.3.0-beta.3", history[0].version.to_string());
+}
+
+#[test]
+fn test_rustc_env_is_emitted_without_updating_manifest() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    let head = repo_test_helper::commit_on_head(&repo, "m3").unwrap().id();
+
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    let manifest = "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n";
+    std::fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+
+    let config = MinverConfig {
+        build_metadata: Some(String::from("build.{height}")),
+        dirty_ignore_untracked: true,
+        ..MinverConfig::default()
+    };
+    let env = build_utils::get_rustc_env(&crate_dir.as_os_str().to_os_string(), &config).unwrap();
+    let head = head.to_string();
+    assert_eq!(
+        vec![
+            ("MINVER_VERSION", "1.2.4-alpha.2+build.2"),
+            ("MINVER_MAJOR", "1"),
+            ("MINVER_MINOR", "2"),
+            ("MINVER_PATCH", "4"),
+            ("MINVER_PRERELEASE", "alpha.2"),
+            ("MINVER_METADATA", "build.2"),
+            ("MINVER_HEIGHT", "2"),
+            ("MINVER_SHA", head.as_str()),
+        ],
+        env.iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        manifest,
+        std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap()
+    );
+}
+
+#[test]
+fn test_rustc_env_uses_manifest_version_outside_of_repository() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion = \"2.0.1\"\n",
+    )
+    .unwrap();
+
+    let config = MinverConfig {
+        discover_repository: false,
+        ..MinverConfig::default()
+    };
+    let env = build_utils::get_rustc_env(&dir.path().as_os_str().to_os_string(), &config).unwrap();
+    assert!(env.contains(&("MINVER_VERSION", String::from("2.0.1"))));
+    assert!(env.contains(&("MINVER_PRERELEASE", String::new())));
+    assert!(env.contains(&("MINVER_SHA", String::new())));
+}
+
+#[test]
+fn test_version_module_is_written() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    let head = repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+
+    let config = MinverConfig {
+        dirty_ignore_untracked: true,
+        ..MinverConfig::default()
+    };
+    let build_info =
+        build_utils::get_build_info(&dir.path().as_os_str().to_os_string(), &config).unwrap();
+    let out_dir = TempDir::new().unwrap();
+    let path = build_info.write_version_module(out_dir.path()).unwrap();
+    assert_eq!(out_dir.path().join(build_utils::VERSION_MODULE_FILE), path);
+
+    let module = std::fs::read_to_string(path).unwrap();
+    for constant in &[
+        String::from("pub const VERSION: &str = \"1.2.4-alpha.1\";"),
+        String::from("pub const MAJOR: u64 = 1;"),
+        String::from("pub const PATCH: u64 = 4;"),
+        String::from("pub const PRERELEASE: Option<&str> = Some(\"alpha.1\");"),
+        String::from("pub const BUILD_METADATA: Option<&str> = None;"),
+        String::from("pub const IS_PRERELEASE: bool = true;"),
+        String::from("pub const HEIGHT: u32 = 1;"),
+        format!("pub const COMMIT: &str = \"{}\";", head.id()),
+        format!(
+            "pub const COMMIT_TIMESTAMP: Option<i64> = Some({});",
+            head.time().seconds()
+        ),
+    ] {
+        assert!(module.contains(constant.as_str()), "{}", module);
+    }
+}
+
+#[test]
+fn test_workspace_versions_are_updated() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m1").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+    repo_test_helper::commit_on_head(&repo, "m2").unwrap();
+
+    let write = |path: &str, contents: &str| {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/old\"]\n\n\
+         [workspace.package]\nversion = \"0.0.0\"\n\n\
+         [workspace.dependencies]\nfoo = { path = \"crates/foo\", version = \"0.0.0\" }\n\
+         serde = \"1\"\n",
+    );
+    write(
+        "crates/foo/Cargo.toml",
+        "[package]\nname = \"foo\"\nversion = { workspace = true }\n",
+    );
+    write(
+        "crates/bar/Cargo.toml",
+        "[package]\nname = \"bar\"\nversion = \"0.0.0\"\n\n\
+         [dependencies]\nfoo = { workspace = true }\n\
+         baz = { path = \"../../tools/cli\", package = \"minver-cli\" }\n\n\
+         [target.'cfg(unix)'.dev-dependencies.foo]\npath = \"../foo\"\n",
+    );
+    write(
+        "crates/qux/Cargo.toml",
+        "[package]\nname = \"qux\"\nversion.workspace = true\n\n\
+         [dependencies]\nfoo.workspace = true\n",
+    );
+    write(
+        "crates/old/Cargo.toml",
+        "[package]\nname = \"old\"\nversion = \"0.0.0\"\n",
+    );
+    write(
+        "tools/cli/Cargo.toml",
+        "[package]\nname = \"minver-cli\"\nversion = \"0.0.0\"\n",
+    );
+    write(
+        "Cargo.lock",
+        "version = 3\n\n[[package]]\nname = \"bar\"\nversion = \"0.0.0\"\n\
+         dependencies = [\n \"foo\",\n]\n\n[[package]]\nname = \"foo\"\nversion = \"0.0.0\"\n",
+    );
+
+    let config = MinverConfig {
+        build_metadata: Some(String::from("build.{height}")),
+        ..MinverConfig::default()
+    };
+    build_utils::update_workspace_version(&dir.path().as_os_str().to_os_string(), &config).unwrap();
+
+    let read = |path: &str| std::fs::read_to_string(dir.path().join(path)).unwrap();
+    let root = read("Cargo.toml");
+    assert!(root.contains("[workspace.package]\nversion = \"1.2.4-alpha.1+build.1\"\n"));
+    assert!(
+        root.contains("foo = { path = \"crates/foo\", version = \"1.2.4-alpha.1\" }"),
+        "{}",
+        root
+    );
+    assert!(root.contains("serde = \"1\""));
+    assert_eq!(
+        "[package]\nname = \"foo\"\nversion = { workspace = true }\n",
+        read("crates/foo/Cargo.toml")
+    );
+    assert_eq!(
+        "[package]\nname = \"qux\"\nversion.workspace = true\n\n\
+         [dependencies]\nfoo.workspace = true\n",
+        read("crates/qux/Cargo.toml")
+    );
+    let bar = read("crates/bar/Cargo.toml");
+    assert!(bar.contains("version = \"1.2.4-alpha.1+build.1\""));
+    assert!(bar.contains("foo = { workspace = true }"));
+    assert!(bar.contains(
+        "baz = { path = \"../../tools/cli\", package = \"minver-cli\", version = \"1.2.4-alpha.1\" }"
+    ));
+    assert!(bar.contains("path = \"../foo\"\nversion = \"1.2.4-alpha.1\"\n"));
+    assert!(read("tools/cli/Cargo.toml").contains("version = \"1.2.4-alpha.1+build.1\""));
+    assert!(read("crates/old/Cargo.toml").contains("version = \"0.0.0\""));
+    assert_eq!(
+        "version = 3\n\n[[package]]\nname = \"bar\"\nversion = \"1.2.4-alpha.1+build.1\"\n\
+         dependencies = [\n \"foo\",\n]\n\n\
+         [[package]]\nname = \"foo\"\nversion = \"1.2.4-alpha.1+build.1\"\n",
+        read("Cargo.lock")
+    );
+}
+
+#[test]
+fn test_update_workspace_version_requires_workspace_table() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\nname = \"foo\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+
+    let err = build_utils::update_workspace_version(
+        &dir.path().as_os_str().to_os_string(),
+        &MinverConfig::default(),
+    )
+    .err();
+    assert!(matches!(
+        err,
+        Some(MinverError::MissingWorkspaceTable { .. })
+    ));
+}
+
+#[test]
+fn test_lockfile_is_updated_with_package_version() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    std::fs::write(
+        crate_dir.join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+    let registry = "registry+https://github.com/rust-lang/crates.io-index";
+    let lockfile = format!(
+        "# This file is automatically @generated by Cargo.\n\
+         version = 3\n\n\
+         [[package]]\nname = \"my_crate\"\nversion = \"0.0.0\"\n\n\
+         [[package]]\nname = \"my_crate\"\nversion = \"0.0.0\"\nsource = \"{0}\"\n\n\
+         [[package]]\nname = \"tool\"\nversion = \"0.1.0\"\ndependencies = [\n \
+         \"my_crate 0.0.0\",\n \"my_crate 0.0.0 ({0})\",\n]\n",
+        registry
+    );
+    std::fs::write(dir.path().join("Cargo.lock"), &lockfile).unwrap();
+
+    build_utils::update_package_version(
+        &crate_dir.as_os_str().to_os_string(),
+        &MinverConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        format!(
+            "# This file is automatically @generated by Cargo.\n\
+             version = 3\n\n\
+             [[package]]\nname = \"my_crate\"\nversion = \"1.2.3\"\n\n\
+             [[package]]\nname = \"my_crate\"\nversion = \"0.0.0\"\nsource = \"{0}\"\n\n\
+             [[package]]\nname = \"tool\"\nversion = \"0.1.0\"\ndependencies = [\n \
+             \"my_crate 1.2.3\",\n \"my_crate 0.0.0 ({0})\",\n]\n",
+            registry
+        ),
+        std::fs::read_to_string(dir.path().join("Cargo.lock")).unwrap()
+    );
+}
+
+#[test]
+fn test_inherited_package_version_is_updated_in_workspace_root() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let root = "[workspace]\nmembers = [\"my_crate\"]\n\n\
+                [workspace.package]\nversion = \"0.0.0\" # set by minver\n";
+    let manifest = "[package]\nname = \"my_crate\"\nversion.workspace = true\n";
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    std::fs::write(dir.path().join("Cargo.toml"), root).unwrap();
+    std::fs::write(crate_dir.join("Cargo.toml"), manifest).unwrap();
+
+    build_utils::update_package_version(
+        &crate_dir.as_os_str().to_os_string(),
+        &MinverConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        manifest,
+        std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap()
+    );
+    assert_eq!(
+        root.replace("0.0.0", "1.2.3"),
+        std::fs::read_to_string(dir.path().join("Cargo.toml")).unwrap()
+    );
+}
+
+#[test]
+fn test_check_package_version_reports_stale_versions() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let manifest = "[package]\nname = \"my_crate\"\nversion = \"0.0.0\"\n";
+    let lockfile = "version = 3\n\n[[package]]\nname = \"my_crate\"\nversion = \"0.0.0\"\n";
+    std::fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+    std::fs::write(dir.path().join("Cargo.lock"), lockfile).unwrap();
+    let manifest_dir = dir.path().as_os_str().to_os_string();
+    let config = MinverConfig::default();
+
+    let mismatches = build_utils::check_package_version(&manifest_dir, &config).unwrap();
+    assert_eq!(
+        vec![
+            build_utils::VersionMismatch {
+                path: dir.path().join("Cargo.toml"),
+                package: String::from("my_crate"),
+                expected: String::from("1.2.3"),
+                actual: Some(String::from("0.0.0")),
+            },
+            build_utils::VersionMismatch {
+                path: dir.path().join("Cargo.lock"),
+                package: String::from("my_crate"),
+                expected: String::from("1.2.3"),
+                actual: Some(String::from("0.0.0")),
+            },
+        ],
+        mismatches
+    );
+    assert!(mismatches[0]
+        .to_string()
+        .ends_with("(my_crate)\n-version = \"0.0.0\"\n+version = \"1.2.3\""));
+    assert_eq!(
+        manifest,
+        std::fs::read_to_string(dir.path().join("Cargo.toml")).unwrap()
+    );
+    assert_eq!(
+        lockfile,
+        std::fs::read_to_string(dir.path().join("Cargo.lock")).unwrap()
+    );
+
+    let config = MinverConfig {
+        version_override: Some(Version::parse("0.0.0").unwrap()),
+        ..MinverConfig::default()
+    };
+    assert!(build_utils::check_package_version(&manifest_dir, &config)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_check_package_version_resolves_inherited_version() {
+    let dir = TempDir::new().unwrap();
+    let repo = repo_test_helper::create_temp_repo(dir.path()).unwrap();
+    repo_test_helper::commit_on_head(&repo, "m").unwrap();
+    repo_test_helper::tag_head(&repo, "1.2.3").unwrap();
+
+    let crate_dir = dir.path().join("my_crate");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    std::fs::write(
+        crate_dir.join("Cargo.toml"),
+        "[package]\nname = \"my_crate\"\nversion.workspace = true\n",
+    )
+    .unwrap();
+    let root_path = dir.path().join("Cargo.toml");
+    let manifest_dir = crate_dir.as_os_str().to_os_string();
+    let config = MinverConfig::default();
+
+    std::fs::write(
+        &root_path,
+        "[workspace]\nmembers = [\"my_crate\"]\n\n[workspace.package]\nversion = \"1.2.3\"\n",
+    )
+    .unwrap();
+    assert!(build_utils::check_package_version(&manifest_dir, &config)
+        .unwrap()
+        .is_empty());
+
+    std::fs::write(
+        &root_path,
+        "[workspace]\nmembers = [\"my_crate\"]\n\n[workspace.package]\nversion = \"1.0.0\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![build_utils::VersionMismatch {
+            path: root_path,
+            package: String::from("my_crate"),
+            expected: String::from("1.2.3"),
+            actual: Some(String::from("1.0.0")),
+        }],
+        build_utils::check_package_version(&manifest_dir, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_cli_exits_with_error_status_when_version_cannot_be_calculated() {
+    let dir = TempDir::new().unwrap();
+    git2::Repository::init(dir.path()).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_minver"))
+        .current_dir(dir.path())
+        .env_remove("GIT_DIR")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Error: HEAD does not point to a commit\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use git2::{Commit, Oid, Repository, Signature};
+use std::fs;
 use std::path::Path;
+use std::process::Command;
 
 pub fn create_temp_repo(path: &Path) -> Result<Repository> {
     let repo = Repository::init(path).unwrap();
@@ -27,7 +29,7 @@ pub fn commit_with_parent<'a>(
     commit: &Commit,
     message: &str,
 ) -> Result<Commit<'a>> {
-    merge_commit(repo, &[&commit], message)
+    merge_commit(repo, &[commit], message)
 }
 
 pub fn merge_commit<'a>(
@@ -42,7 +44,36 @@ pub fn merge_commit<'a>(
         &signature,
         message,
         &parents[0].tree()?,
-        &parents,
+        parents,
+    )?;
+    Ok(repo.find_commit(commit_id)?)
+}
+
+pub fn commit_file_on_head<'a>(
+    repo: &'a Repository,
+    path: &str,
+    content: &str,
+    message: &str,
+) -> Result<Commit<'a>> {
+    let head_commit = get_head(repo)?;
+
+    let file_path = repo.workdir().unwrap().join(path);
+    fs::create_dir_all(file_path.parent().unwrap())?;
+    fs::write(&file_path, content)?;
+
+    let mut index = repo.index()?;
+    index.read_tree(&head_commit.tree()?)?;
+    index.add_path(Path::new(path))?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let signature = Signature::now("testName", "test@example.com")?;
+    let commit_id = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &[&head_commit],
     )?;
     Ok(repo.find_commit(commit_id)?)
 }
@@ -61,6 +92,20 @@ pub fn checkout_commit(repo: &Repository, commit: &Commit) -> Result<()> {
     Ok(repo.set_head_detached(commit.id())?)
 }
 
-fn get_head(repo: &Repository) -> Result<Commit> {
+/// Clones the repository into `path` with `git clone --depth`, so that only the latest `depth`
+/// commits of HEAD and the tags pointing to them are fetched.
+pub fn clone_shallow(repo: &Repository, path: &Path, depth: u32) -> Result<Repository> {
+    let status = Command::new("git")
+        .arg("clone")
+        .arg("--quiet")
+        .arg(format!("--depth={}", depth))
+        .arg(format!("file://{}", repo.workdir().unwrap().display()))
+        .arg(path)
+        .status()?;
+    anyhow::ensure!(status.success(), "git clone failed with {}", status);
+    Ok(Repository::open(path)?)
+}
+
+fn get_head(repo: &Repository) -> Result<Commit<'_>> {
     Ok(repo.head()?.peel_to_commit()?)
 }